@@ -1,85 +1,255 @@
-use std::{error::Error, collections::{HashSet, HashMap}, hash::Hash};
+#![allow(clippy::needless_return)]
+
+use std::{error::Error, fmt::Display};
 
 use clap::{App, Arg};
 
 
 
+#[derive(Debug)]
 struct SyntaxError {
+    /// Character index of the offending delimiter.
     position: usize,
-    expected: Option<char>,
-    offending_character: char
+    expected: Option<String>,
+    offending: String,
+    /// Character index of the opener of the innermost open pair, if any.
+    opened_at: Option<usize>
 }
 
 impl SyntaxError {    
-    fn new(position: usize, offending_character: char, expected: Option<char>) -> Self {
+    fn new(position: usize, offending: &str, expected: Option<&str>, opened_at: Option<usize>) -> Self {
         Self {
             position,
-            expected,
-            offending_character
+            expected: expected.map(|x| x.to_string()),
+            offending: offending.to_string(),
+            opened_at
+        }
+    }
+
+    /// The line with a caret under the offending delimiter and a dash under the unmatched opener.
+    fn get_diagnostic(&self, line: &str) -> String {
+        let mut markers: Vec<char> = vec![' '; line.chars().count().max(self.position + 1)];
+
+        if let Some(opened_at) = self.opened_at {
+            markers[opened_at] = '-';
+        }
+        for marker in markers.iter_mut().skip(self.position).take(self.offending.chars().count()) {
+            *marker = '^';
         }
+
+        let mut diagnostic = format!("{}\n{}\n", line, markers.iter().collect::<String>().trim_end());
+
+        match (&self.expected, self.opened_at) {
+            (Some(expected), Some(opened_at)) => diagnostic += &format!("expected \"{}\" to close the pair opened at column {}\n", expected, opened_at + 1),
+            _ => diagnostic += "no pair is open here\n",
+        }
+
+        return diagnostic;
+    }
+}
+
+/// Columns count from 1.
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.expected {
+            Some(expected) => write!(f, "Expected \"{}\", found \"{}\" at column {}", expected, self.offending, self.position + 1),
+            None => write!(f, "Unexpected \"{}\" at column {}", self.offending, self.position + 1),
+        }
+    }
+}
+
+impl Error for SyntaxError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct BracketPair {
+    open: String,
+    close: String,
+    /// Everything up to the closer is skipped unchecked, as in comments or string literals.
+    verbatim: bool
+}
+
+impl BracketPair {
+    fn new(open: &str, close: &str) -> Self {
+        return BracketPair {
+            open: open.to_string(),
+            close: close.to_string(),
+            verbatim: false
+        };
+    }
+}
+
+/// The bracket pairs a syntax checker knows about.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Grammar {
+    pairs: Vec<BracketPair>
+}
+
+impl Grammar {
+    fn standard() -> Self {
+        return Grammar {
+            pairs: vec![
+                BracketPair::new("(", ")"),
+                BracketPair::new("[", "]"),
+                BracketPair::new("{", "}"),
+                BracketPair::new("<", ">"),
+            ]
+        };
     }
+}
+
+/// One pair per line, opener and closer separated by whitespace and optionally followed by
+/// `verbatim`, e.g. `/* */ verbatim`. Lines starting with `#` are comments.
+impl TryFrom<&str> for Grammar {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut pairs = vec![];
+
+        for (line_number, line) in value.lines().enumerate() {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+
+            let pair = match parts.as_slice() {
+                [open, close] => BracketPair::new(open, close),
+                [open, close, "verbatim"] => BracketPair {
+                    verbatim: true,
+                    ..BracketPair::new(open, close)
+                },
+                _ => return Err(format!("Failed to parse bracket pair on line {}: \"{}\"", line_number + 1, line).into()),
+            };
 
+            if pairs.iter().any(|other: &BracketPair| other.open == pair.open) {
+                return Err(format!("Opener \"{}\" on line {} is defined twice", pair.open, line_number + 1).into());
+            }
+
+            pairs.push(pair);
+        }
+
+        if pairs.is_empty() {
+            return Err("Grammar doesn't define any bracket pairs".into());
+        }
+
+        return Ok(Grammar {
+            pairs
+        });
+    }
 }
 
 struct SyntaxChecker {
-    map_open_to_closing: HashMap<char,char>
+    grammar: Grammar,
+    /// Skip characters that aren't delimiters instead of treating them as stray closers.
+    ignore_other_characters: bool
 }
 
 impl SyntaxChecker {
-    fn new() -> Self {
-        let mut map_open_to_closing: HashMap<char, char> = HashMap::new();
-        map_open_to_closing.insert('(', ')');
-        map_open_to_closing.insert('[', ']');
-        map_open_to_closing.insert('{', '}');
-        map_open_to_closing.insert('<', '>');
-
+    fn with_grammar(grammar: Grammar, ignore_other_characters: bool) -> Self {
         return Self {
-            map_open_to_closing
+            grammar,
+            ignore_other_characters
         }
     }
 
-    fn parse_line(&self, line: &str) -> Result<Vec<char>,SyntaxError>  {
-        let mut parser_stack: Vec<char> = vec![];
-        
-        for (pos, read) in line.chars().enumerate() {
-            if self.map_open_to_closing.contains_key(&read) {
-                parser_stack.push(read);
-            } else {
-                let stack_top = match parser_stack.pop() {
-                    Some(x) => x,
-                    None => return Err(SyntaxError::new(pos, read, None)),
-                };
-
-                let expected = match self.map_open_to_closing.get(&stack_top) {
-                    Some(x) => x,
-                    None => return Err(SyntaxError::new(pos, read, None)),
-                };
-
-                if read != *expected {
-                    return Err(SyntaxError::new(pos, read, Some(*expected)));
-                }
-            }
+    /// Index of the pair with the longest opener (or closer) at the start of `rest`.
+    fn find_longest(&self, rest: &str, delimiter: fn(&BracketPair) -> &str) -> Option<usize> {
+        return self.grammar.pairs
+            .iter()
+            .enumerate()
+            .filter(|(_, pair)| rest.starts_with(delimiter(pair)))
+            .max_by_key(|(_, pair)| delimiter(pair).len())
+            .map(|(index, _)| index);
+    }
+
+    /// Stops at the first error, otherwise returns the closers completing the line.
+    fn parse_line(&self, line: &str) -> Result<Vec<String>, SyntaxError> {
+        let (errors, completion) = self.check_line(line, false);
+
+        return match errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(completion),
+        };
+    }
+
+    /// Reports every error of the line. A mismatched closer closes the innermost pair it
+    /// belongs to, dropping the pairs opened within; a closer without an open pair is skipped.
+    fn parse_line_recovering(&self, line: &str) -> (Vec<SyntaxError>, Vec<String>) {
+        return self.check_line(line, true);
+    }
+
+    fn check_line(&self, line: &str, recover: bool) -> (Vec<SyntaxError>, Vec<String>) {
+        let mut errors = vec![];
+        // open pairs along with the position of their opener
+        let mut parser_stack: Vec<(usize, usize)> = vec![];
+        let mut offset = 0;
+        let mut pos = 0;
+
+        while offset < line.len() {
+            let rest = &line[offset..];
+            let expected = parser_stack.last().map(|(pair, _)| &self.grammar.pairs[*pair]);
+
+            // the closer of the innermost pair wins over openers, so identical quotes close
+            let token = match expected {
+                Some(pair) if rest.starts_with(&pair.close) => {
+                    parser_stack.pop();
+                    pair.close.as_str()
+                },
+                Some(pair) if pair.verbatim => &rest[..rest.chars().next().unwrap().len_utf8()],
+                _ => match self.find_longest(rest, |pair| &pair.open) {
+                    Some(opened) => {
+                        parser_stack.push((opened, pos));
+                        self.grammar.pairs[opened].open.as_str()
+                    },
+                    None => {
+                        let closed = self.find_longest(rest, |pair| &pair.close);
+                        let token = match closed {
+                            Some(closed) => self.grammar.pairs[closed].close.as_str(),
+                            None => &rest[..rest.chars().next().unwrap().len_utf8()],
+                        };
+
+                        if closed.is_some() || !self.ignore_other_characters {
+                            let opened_at = parser_stack.last().map(|(_, opened_at)| *opened_at);
+                            errors.push(SyntaxError::new(pos, token, expected.map(|pair| pair.close.as_str()), opened_at));
+
+                            if !recover {
+                                return (errors, vec![]);
+                            }
+
+                            if let Some(open) = parser_stack.iter().rposition(|(pair, _)| Some(*pair) == closed) {
+                                parser_stack.truncate(open);
+                            }
+                        }
+
+                        token
+                    },
+                },
+            };
+
+            offset += token.len();
+            pos += token.chars().count();
         }
 
-        let completion = parser_stack.into_iter().rev().map(|x| self.map_open_to_closing.get(&x).unwrap()).copied().collect();
+        let completion = parser_stack.into_iter().rev().map(|(pair, _)| self.grammar.pairs[pair].close.clone()).collect();
 
-        Ok(completion)
+        return (errors, completion);
     }
 }
 
 fn score_syntax_errors(errors: Vec<SyntaxError>) -> usize {
     errors
     .iter()
-    .map(|e| match e.offending_character {
-        ')' => 3,
-        ']' => 57,
-        '}' => 1197,
-        '>' => 25137,
+    .map(|e| match e.offending.as_str() {
+        ")" => 3,
+        "]" => 57,
+        "}" => 1197,
+        ">" => 25137,
         _ => 0
     })
     .sum()
 }
 
+#[allow(clippy::ptr_arg)]
 fn score_completion(completion: &Vec<char>) -> usize {
     let mut score = 0;
     for character in completion {
@@ -96,10 +266,11 @@ fn score_completion(completion: &Vec<char>) -> usize {
     score
 }
 
-fn score_completions(completions: Vec<Vec<char>>) -> usize {
+/// Middle score of all completions, `None` if no line needs completing.
+fn score_completions(completions: Vec<Vec<char>>) -> Option<usize> {
     let mut scores: Vec<usize> = completions.iter().map(score_completion).collect();
     scores.sort();
-    return scores[scores.len() / 2];
+    return scores.get(scores.len() / 2).copied();
 }
 
 fn main() {
@@ -108,28 +279,75 @@ fn main() {
                         .help("Input file to parse.")
                         .required(true)
                         .index(1))
+                    .arg(Arg::with_name("GRAMMAR")
+                        .help("File with one bracket pair per line, e.g. \"/* */ verbatim\", instead of the four puzzle pairs.")
+                        .long("grammar")
+                        .takes_value(true)
+                        .value_name("FILE"))
+                    .arg(Arg::with_name("IGNORE_OTHER")
+                        .help("Skip characters that aren't part of a bracket pair instead of reporting them.")
+                        .long("ignore-other"))
+                    .arg(Arg::with_name("ALL_ERRORS")
+                        .help("Recover from syntax errors and score every error of a line instead of only the first.")
+                        .long("all-errors"))
+                    .arg(Arg::with_name("CHECK")
+                        .help("Print a diagnostic for every corrupted line, pointing at the offending and the unmatched opening delimiter.")
+                        .long("check"))
                     .get_matches();
 
-    let input = std::fs::read_to_string(&matches.value_of("INPUT").unwrap())
+    let input = std::fs::read_to_string(matches.value_of("INPUT").unwrap())
         .expect("Failed to open the input file");
 
-    let checker = SyntaxChecker::new();
+    let grammar = match matches.value_of("GRAMMAR") {
+        Some(path) => std::fs::read_to_string(path)
+            .expect("Failed to open the grammar")
+            .as_str()
+            .try_into()
+            .unwrap(),
+        None => Grammar::standard(),
+    };
+
+    let checker = SyntaxChecker::with_grammar(grammar, matches.is_present("IGNORE_OTHER"));
     let mut errors: Vec<SyntaxError> = vec![];
     let mut completions: Vec<Vec<char>> = vec![];
     
-    for line in input.lines() {
-        match checker.parse_line(line) {
-            Ok(completion) => {
-                if completion.len() > 0 {
-                    completions.push(completion);
-                }
+    let mut corrupted_lines = 0;
+    
+    for (line_number, line) in input.lines().enumerate() {
+        let (line_errors, completion) = match matches.is_present("ALL_ERRORS") {
+            true => checker.parse_line_recovering(line),
+            false => match checker.parse_line(line) {
+                Ok(completion) => (vec![], completion),
+                Err(e) => (vec![e], vec![]),
             },
-            Err(e) =>  errors.push(e),
+        };
+
+        if line_errors.is_empty() {
+            if !completion.is_empty() {
+                completions.push(completion.concat().chars().collect());
+            }
+        } else {
+            corrupted_lines += 1;
+
+            if matches.is_present("CHECK") {
+                for error in line_errors.iter() {
+                    println!("Line {}: {}", line_number + 1, error);
+                    println!("{}", error.get_diagnostic(line));
+                }
+            }
+
+            errors.extend(line_errors);
         }
     }
 
+    if matches.is_present("ALL_ERRORS") {
+        println!("Syntax errors: {} in {} lines", errors.len(), corrupted_lines);
+    }
     println!("Error score: {}", score_syntax_errors(errors));
-    println!("Completions score: {}", score_completions(completions));
+    match score_completions(completions) {
+        Some(score) => println!("Completions score: {}", score),
+        None => println!("Completions score: no incomplete lines"),
+    }
 }
 
 #[cfg(test)]
@@ -147,7 +365,7 @@ r"[({(<(())[]>[[{[]{<()<>>
 
 #[test]
 fn test_example_errors() {
-    let checker = SyntaxChecker::new();
+    let checker = SyntaxChecker::with_grammar(Grammar::standard(), false);
 
     let errors: Vec<SyntaxError> = EXAMPLE_INPUT.lines()
         .map(|x| checker.parse_line(x))
@@ -169,7 +387,7 @@ fn test_completion_scoring() {
 
 #[test]
 fn test_completion_vector_scoring() {
-    let completions: Vec<Vec<char>> = vec![
+    let completions: Vec<Vec<char>> = [
         "}}]])})]", 
         ")}>]})", 
         "}}>}>))))", 
@@ -177,7 +395,84 @@ fn test_completion_vector_scoring() {
         "])}>"
     ].iter().map(|x| x.chars().collect()).collect();
 
-    assert_eq!(score_completions(completions), 288957);
+    assert_eq!(score_completions(completions), Some(288957));
+    assert_eq!(score_completions(vec![]), None);
+
 
+}
+
+#[test]
+fn test_custom_grammar() {
+    let grammar: Grammar = "( )\n\" \" verbatim\n/* */ verbatim\nbegin end\n| |".try_into().unwrap();
+    let checker = SyntaxChecker::with_grammar(grammar.clone(), true);
+
+    assert_eq!(checker.parse_line("f(\"a(b\") /* ) */").unwrap(), Vec::<String>::new());
+    assert_eq!(checker.parse_line("begin f(x) \"(\" (").unwrap(), vec![")", "end"]);
+
+    // identical delimiters close when they match the innermost pair, and open otherwise
+    assert!(checker.parse_line("|()|").unwrap().is_empty());
+    assert_eq!(checker.parse_line("|(|").unwrap(), vec!["|", ")", "|"]);
+    assert_eq!(checker.parse_line("|(|)|").unwrap_err().expected, Some("|".to_string()));
+
+    let error = checker.parse_line("begin (x end").unwrap_err();
+    assert_eq!(error.position, 9);
+    assert_eq!(error.offending, "end");
+    assert_eq!(error.expected, Some(")".to_string()));
+
+    // without ignoring, anything that isn't an opener is a stray closer
+    let strict = SyntaxChecker::with_grammar(grammar, false);
+    let error = strict.parse_line("(x)").unwrap_err();
+    assert_eq!((error.position, error.offending.as_str()), (1, "x"));
+
+    assert!(Grammar::try_from("( ) extra").is_err());
+    assert!(Grammar::try_from("( )\n( ]").is_err());
+    assert!(Grammar::try_from("# nothing").is_err());
+}
+
+#[test]
+fn test_error_recovery() {
+    let checker = SyntaxChecker::with_grammar(Grammar::standard(), false);
+
+    // the stray ] is skipped, the } closes the { and drops the [ opened within
+    let (errors, completion) = checker.parse_line_recovering("(]{[}");
+    assert_eq!(errors.len(), 2);
+    assert_eq!((errors[0].position, errors[0].offending.as_str(), errors[0].expected.as_deref()), (1, "]", Some(")")));
+    assert_eq!((errors[1].position, errors[1].offending.as_str(), errors[1].expected.as_deref()), (4, "}", Some("]")));
+    assert_eq!(completion, vec![")"]);
 
-}
\ No newline at end of file
+    let (errors, completion) = checker.parse_line_recovering("{([(<{}[<>[]}>{[]{[(<()>");
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].offending, "}");
+    assert_eq!(errors[1].offending, ">");
+    assert_eq!(completion, vec![")", "]", "}", "}"]);
+
+    // the first error is the one the stopping parser reports
+    for line in EXAMPLE_INPUT.lines() {
+        let (errors, _) = checker.parse_line_recovering(line);
+        match checker.parse_line(line) {
+            Ok(_) => assert!(errors.is_empty()),
+            Err(e) => assert_eq!((e.position, e.offending), (errors[0].position, errors[0].offending.clone())),
+        }
+    }
+}
+
+#[test]
+fn test_diagnostics() {
+    let checker = SyntaxChecker::with_grammar(Grammar::standard(), false);
+
+    let error = checker.parse_line("{([(<{}[<>[]}>{[]{[(<()>").unwrap_err();
+    assert_eq!(error.to_string(), "Expected \"]\", found \"}\" at column 13");
+    assert_eq!(error.get_diagnostic("{([(<{}[<>[]}>{[]{[(<()>"), concat!(
+        "{([(<{}[<>[]}>{[]{[(<()>\n",
+        "       -    ^\n",
+        "expected \"]\" to close the pair opened at column 8\n",
+    ));
+
+    let error = checker.parse_line("()]").unwrap_err();
+    assert_eq!(error.to_string(), "Unexpected \"]\" at column 3");
+    assert_eq!(error.get_diagnostic("()]"), "()]\n  ^\nno pair is open here\n");
+
+    let grammar: Grammar = "begin end\n( )".try_into().unwrap();
+    let error = SyntaxChecker::with_grammar(grammar, true).parse_line("begin (x end").unwrap_err();
+    assert_eq!(error.get_diagnostic("begin (x end").lines().nth(1), Some("      -  ^^^"));
+}
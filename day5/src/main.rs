@@ -1,165 +1,365 @@
-use std::{error::Error, cmp::{min, max}, ops::{Sub, AddAssign}};
+#![allow(clippy::needless_return)]
+
+use std::{cmp::{max, min}, collections::HashMap, error::Error, fmt::Display};
 
 use clap::{App, Arg};
-use regex::{self, Regex};
+use geometry::{BoundingBox, Line2D, LineClass, Vector2D};
 
+/// How many lines have to cover a point for it to be reported.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-struct Vector2D {
-    pub x: i32,
-    pub y: i32
+enum OverlapThreshold {
+    AtLeast(u32),
+    Exactly(u32)
 }
 
-impl Vector2D {
-    fn magnitude(&self) -> f64 {
-        let fx: f64 = self.x.pow(2).try_into().unwrap();
-        let fy: f64 = self.y.pow(2).try_into().unwrap();
-        return (fx + fy).sqrt();
-    }
+impl OverlapThreshold {
+    /// Counts below 1 are rejected, points no line covers are never reported.
+    fn parse(value: &str, exact: bool) -> Result<Self, Box<dyn Error>> {
+        let count: u32 = value.parse()?;
+
+        if count == 0 {
+            return Err(format!("Overlap count has to be at least 1, got {}", count).into());
+        }
 
-    fn new(x: i32, y: i32) -> Self {
-        return Vector2D {
-            x,y
+        return match exact {
+            true => Ok(OverlapThreshold::Exactly(count)),
+            false => Ok(OverlapThreshold::AtLeast(count)),
         };
     }
+
+    fn matches(&self, count: u32) -> bool {
+        match self {
+            OverlapThreshold::AtLeast(minimum) => count >= *minimum,
+            OverlapThreshold::Exactly(expected) => count == *expected,
+        }
+    }
+
+    /// Whether points covered by a single line can match, which rules out pairwise intersection.
+    fn includes_single_lines(&self) -> bool {
+        return self.matches(1);
+    }
 }
 
-impl AddAssign<Vector2D> for Vector2D {
-    fn add_assign(&mut self, rhs: Vector2D) {
-        self.x += rhs.x;
-        self.y += rhs.y;
+fn main() {
+    let matches = App::new("Advent of Code Day 5")
+                    .arg(Arg::with_name("INPUT")
+                        .help("Sonar readings file to parse.")
+                        .required(true)
+                        .index(1))
+                    .arg(Arg::with_name("RENDER")
+                        .help("Print the overlap map of all lines, as shown in the puzzle text.")
+                        .long("render"))
+                    .arg(Arg::with_name("HEATMAP")
+                        .help("Write the overlap map of all lines as a greyscale PGM heat-map.")
+                        .long("heatmap")
+                        .takes_value(true)
+                        .value_name("FILE"))
+                    .arg(Arg::with_name("HEATMAP_SIZE")
+                        .help("Maximum width and height of the heat-map in pixels.")
+                        .long("heatmap-size")
+                        .takes_value(true)
+                        .default_value("1024"))
+                    .arg(Arg::with_name("MIN_OVERLAP")
+                        .help("Minimum number of lines covering a point for it to count, defaults to 2.")
+                        .long("min-overlap")
+                        .takes_value(true)
+                        .conflicts_with("EXACT_OVERLAP"))
+                    .arg(Arg::with_name("EXACT_OVERLAP")
+                        .help("Exact number of lines covering a point for it to count.")
+                        .long("exact-overlap")
+                        .takes_value(true))
+                    .arg(Arg::with_name("CLASSES")
+                        .help("Only consider lines of these classes: horizontal, vertical, diagonal, arbitrary.")
+                        .long("classes")
+                        .takes_value(true)
+                        .multiple(true)
+                        .use_delimiter(true))
+                    .arg(Arg::with_name("POINTS")
+                        .help("List the qualifying points as well.")
+                        .long("points"))
+                    .get_matches();
+
+    let input = std::fs::read_to_string(matches.value_of("INPUT").unwrap())
+        .expect("Failed to open the readings file");
+
+    let lines: Vec<Line2D> = input
+        .split('\n')
+        .filter(|x| !x.is_empty())
+        .map(|x| x.try_into().unwrap())
+        .collect();
+
+    let threshold = match matches.value_of("EXACT_OVERLAP") {
+        Some(exact) => OverlapThreshold::parse(exact, true),
+        None => OverlapThreshold::parse(matches.value_of("MIN_OVERLAP").unwrap_or("2"), false),
+    }.unwrap();
+
+    let runs: Vec<(String, Vec<LineClass>)> = match matches.values_of("CLASSES") {
+        Some(classes) => {
+            let classes: Vec<LineClass> = classes.map(|x| x.try_into().unwrap()).collect();
+            vec![(format!("{:?}", classes), classes)]
+        },
+        None => vec![
+            ("axis aligned only".to_string(), vec![LineClass::Horizontal, LineClass::Vertical]),
+            ("including diagonals".to_string(), vec![LineClass::Horizontal, LineClass::Vertical, LineClass::Diagonal, LineClass::Arbitrary]),
+        ],
+    };
+
+    for (description, classes) in runs {
+        let filtered_lines: Vec<Line2D> = lines.iter().filter(|line| classes.contains(&line.get_class())).cloned().collect();
+        let points = find_overlaps(&filtered_lines, threshold);
+
+        println!("Number of Points with overlaps, {}: {}", description, points.len());
+
+        if matches.is_present("POINTS") {
+            for point in points {
+                println!("{},{}", point.x, point.y);
+            }
+        }
+    }
+
+    if matches.is_present("RENDER") {
+        let bounds = get_bounds(&lines);
+
+        if bounds.get_width() > RENDER_LIMIT || bounds.get_height() > RENDER_LIMIT {
+            eprintln!("Field is too large to render as text, use --heatmap instead");
+        } else {
+            print!("{}", OverlapGrid::new(&lines, 1));
+        }
+    }
+
+    if let Some(heatmap_path) = matches.value_of("HEATMAP") {
+        let heatmap_size: usize = matches.value_of("HEATMAP_SIZE").unwrap().parse().unwrap();
+        let grid = OverlapGrid::with_max_dimension(&lines, heatmap_size);
+
+        std::fs::write(heatmap_path, grid.to_pgm())
+            .expect("Failed to write the heat-map");
     }
 }
 
-impl Sub<Vector2D> for Vector2D {
-    type Output = Vector2D;
+/// Side length up to which `--render` prints the field as text.
+const RENDER_LIMIT: i64 = 200;
 
-    fn sub(self, rhs: Vector2D) -> Self::Output {
-        return Vector2D {
-            x: self.x - rhs.x,
-            y: self.y - rhs.y
-        };
+/// Area up to which the overlaps are counted on a dense grid instead of pairwise.
+const DENSE_AREA_LIMIT: i128 = 1 << 22;
+
+/// Points covered by a number of lines matching `threshold`, sorted by x, then y.
+fn find_overlaps(lines: &[Line2D], threshold: OverlapThreshold) -> Vec<Vector2D> {
+    if get_bounds(lines).get_area() <= DENSE_AREA_LIMIT {
+        return find_overlaps_dense(lines, threshold);
+    } else {
+        return find_overlaps_sparse(lines, threshold);
     }
 }
 
-#[derive(Clone)]
-struct Line2D {
-    pub from: Vector2D,
-    pub to: Vector2D
+fn get_bounds(lines: &[Line2D]) -> BoundingBox {
+    return lines
+        .iter()
+        .map(|line| line.get_bounding_box())
+        .reduce(|acc, bounds| acc.union(&bounds))
+        .unwrap_or(BoundingBox::new(Vector2D::zero(), Vector2D::zero()));
 }
 
-impl Line2D {
-    fn new(from: Vector2D, to: Vector2D) -> Self {
-        return Line2D {
-            from,
-            to
-        };
-    }
+/// Rasterises all lines onto a grid spanning their bounding box.
+fn find_overlaps_dense(lines: &[Line2D], threshold: OverlapThreshold) -> Vec<Vector2D> {
+    return OverlapGrid::new(lines, 1).get_points(threshold);
+}
+
+/// Number of lines covering each point of the field, binned into square cells of `cell_size` points
+/// that keep the count of their most covered point.
+struct OverlapGrid {
+    // indexed x, y
+    cells: Vec<Vec<u32>>,
+    origin: Vector2D,
+    cell_size: i64
+}
 
-    fn get_integer_step_vector(&self) -> Vector2D {
-        let dir_vector = self.to - self.from;
-        
-        return Vector2D {
-            x: Line2D::calc_step_component(dir_vector.x, dir_vector.magnitude()),
-            y: Line2D::calc_step_component(dir_vector.y, dir_vector.magnitude())
+impl OverlapGrid {
+    fn new(lines: &[Line2D], cell_size: i64) -> Self {
+        let bounds = get_bounds(lines);
+
+        let area_width: usize = ((bounds.get_width() - 1) / cell_size + 1).try_into().unwrap();
+        let area_height: usize = ((bounds.get_height() - 1) / cell_size + 1).try_into().unwrap();
+
+        let mut cells = vec![vec![0; area_height]; area_width];
+        let get_cell = |point: Vector2D| {
+            let cell_x = (point.x as i64 - bounds.min.x as i64) / cell_size;
+            let cell_y = (point.y as i64 - bounds.min.y as i64) / cell_size;
+            (cell_x as usize, cell_y as usize)
         };
-    }
 
-    fn calc_step_component(component: i32, magnitude: f64) -> i32 {
-        let normalized = (component as f64) / magnitude;
-        if normalized < 0f64 {
-            return normalized.floor() as i32;
-        } else if normalized > 0f64 {
-            return normalized.ceil() as i32;
+        if cell_size == 1 {
+            for point in lines.iter().flat_map(|line| line.get_points()) {
+                let (cell_x, cell_y) = get_cell(point);
+                cells[cell_x][cell_y] += 1;
+            }
         } else {
-            return 0;
+            // a cell spans several points, so walking the points would take as long as the lines are;
+            // lines only mark the cells they pass through and the pairwise intersections add the overlaps
+            for line in lines {
+                for point in OverlapGrid::get_cell_entry_points(line, bounds.min, cell_size) {
+                    let (cell_x, cell_y) = get_cell(point);
+                    cells[cell_x][cell_y] = max(cells[cell_x][cell_y], 1);
+                }
+            }
+
+            for (point, count) in get_shared_points(lines) {
+                let (cell_x, cell_y) = get_cell(point);
+                cells[cell_x][cell_y] = max(cells[cell_x][cell_y], count);
+            }
         }
+
+        return OverlapGrid {
+            cells,
+            origin: bounds.min,
+            cell_size
+        };
     }
-}
 
-impl TryFrom<&str> for Line2D {
-    type Error = Box<dyn Error>;
+    /// First lattice point of `line` in each cell it passes through, jumping from cell to cell.
+    fn get_cell_entry_points(line: &Line2D, origin: Vector2D, cell_size: i64) -> Vec<Vector2D> {
+        let step = line.get_integer_step_vector();
+        let step_count = line.get_step_count() as i64;
+
+        // steps needed to get from `position` past the border of its cell along one axis
+        let steps_to_leave = |position: i32, origin: i32, step: i32| -> i64 {
+            let offset = position as i64 - origin as i64;
+            let distance = match step {
+                0 => return i64::MAX,
+                step if step > 0 => (offset / cell_size + 1) * cell_size - offset,
+                _ => offset % cell_size + 1,
+            };
+            let step = (step as i64).abs();
+
+            (distance + step - 1) / step
+        };
 
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let line_regex = Regex::new(r"^(?P<x1>[-]{0,1}\d+),(?P<y1>[-]{0,1}\d+) -> (?P<x2>[-]{0,1}\d+),(?P<y2>[-]{0,1}\d+)$").unwrap();
+        let mut points = vec![];
+        let mut k = 0;
 
-        if !line_regex.is_match(&value) {
-            return Err(format!("Input didn't conform to regex").into());
-        } else {
-            let caps = line_regex.captures_iter(&value).next().unwrap();
-
-            Ok(
-                Line2D::new(Vector2D{
-                        x: caps["x1"].parse::<i32>().unwrap(),
-                        y: caps["y1"].parse::<i32>().unwrap(),
-                    },
-                    Vector2D {
-                        x: caps["x2"].parse::<i32>().unwrap(),
-                        y: caps["y2"].parse::<i32>().unwrap(),
-                    }
-                )
-            )
+        while k <= step_count {
+            let point = line.from + step * k as i32;
+            points.push(point);
+
+            k += min(steps_to_leave(point.x, origin.x, step.x), steps_to_leave(point.y, origin.y, step.y));
         }
+
+        return points;
     }
-}
 
-fn main() {
-    let matches = App::new("Advent of Code Day 5")
-                    .arg(Arg::with_name("INPUT")
-                        .help("Sonar readings file to parse.")
-                        .required(true)
-                        .index(1))
-                    .get_matches();
+    /// Picks the cell size so that neither side of the grid exceeds `max_dimension` cells.
+    fn with_max_dimension(lines: &[Line2D], max_dimension: usize) -> Self {
+        let bounds = get_bounds(lines);
+        let extent = max(bounds.get_width(), bounds.get_height());
+        let max_dimension = max(max_dimension, 1) as i64;
+        let cell_size = (extent + max_dimension - 1) / max_dimension;
 
-    let input = std::fs::read_to_string(&matches.value_of("INPUT").unwrap())
-        .expect("Failed to open the readings file");
+        return OverlapGrid::new(lines, cell_size);
+    }
 
-    let lines: Vec<Line2D> = input
-        .split('\n')
-        .filter(|x| !x.is_empty())
-        .map(|x| x.try_into().unwrap())
-        .collect();
+    fn get_width(&self) -> usize {
+        return self.cells.len();
+    }
 
-    let axis_aligned_lines: Vec<Line2D> = lines.clone().into_iter().filter(|line| line.from.x == line.to.x || line.from.y == line.to.y).collect();
+    fn get_height(&self) -> usize {
+        return self.cells[0].len();
+    }
 
-    println!("Number of Points with overlaps, axis aligned only: {}", calculate_intersections(axis_aligned_lines));
-    println!("Number of Points with overlaps, including diagonals: {}", calculate_intersections(lines));
-}
+    /// Lower left corner of every cell whose count matches `threshold`.
+    fn get_points(&self, threshold: OverlapThreshold) -> Vec<Vector2D> {
+        let mut points = vec![];
+
+        for (x, column) in self.cells.iter().enumerate() {
+            for (y, count) in column.iter().enumerate() {
+                if threshold.matches(*count) {
+                    // cell corners lie inside the bounding box, so they fit back into i32
+                    points.push(Vector2D::new(
+                        (self.origin.x as i64 + x as i64 * self.cell_size) as i32,
+                        (self.origin.y as i64 + y as i64 * self.cell_size) as i32
+                    ));
+                }
+            }
+        }
+
+        return points;
+    }
 
-fn calculate_intersections(axis_aligned_lines: Vec<Line2D>) -> usize {
-    let mut x_min = 0;
-    let mut x_max = 0;
-    let mut y_min = 0;
-    let mut y_max = 0;
+    /// Binary greyscale PGM, brightness scaled to the highest count in the grid.
+    fn to_pgm(&self) -> Vec<u8> {
+        let max_count = max(1, *self.cells.iter().flatten().max().unwrap()) as u64;
+        let mut image = format!("P5\n{} {}\n255\n", self.get_width(), self.get_height()).into_bytes();
 
-    for line in axis_aligned_lines.iter() {
-        x_min = min(x_min, min(line.from.x, line.to.x));
-        x_max = max(x_max, max(line.from.x, line.to.x));
-        y_min = min(y_min, min(line.from.y, line.to.y));
-        y_max = max(y_max, max(line.from.y, line.to.y));
+        for y in 0..self.get_height() {
+            for x in 0..self.get_width() {
+                image.push((self.cells[x][y] as u64 * 255 / max_count) as u8);
+            }
+        }
+
+        return image;
     }
+}
 
-    let area_width: usize = (x_max - x_min + 1).try_into().unwrap();
-    let area_height: usize = (y_max - y_min + 1).try_into().unwrap();
+impl Display for OverlapGrid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for y in 0..self.get_height() {
+            for x in 0..self.get_width() {
+                match self.cells[x][y] {
+                    0 => write!(f, ".")?,
+                    count @ 1..=9 => write!(f, "{}", count)?,
+                    _ => write!(f, "+")?,
+                }
+            }
+            writeln!(f)?;
+        }
 
-    let mut area = vec![vec![0;area_height]; area_width];
+        Ok(())
+    }
+}
 
-    for line in axis_aligned_lines.iter() {
-        let mut current_pos = Vector2D::new(line.from.x - x_min, line.from.y - y_min);
-        let target_pos = Vector2D::new(line.to.x - x_min, line.to.y - y_min);
-        let direction = line.get_integer_step_vector();
-        
-        area[current_pos.x as usize][current_pos.y as usize] += 1;
-        loop {
-            current_pos += direction;
-            area[current_pos.x as usize][current_pos.y as usize] += 1;
+/// Number of lines through every point that at least two of them share, found by intersecting every pair.
+fn get_shared_points(lines: &[Line2D]) -> HashMap<Vector2D, u32> {
+    let mut pair_counts: HashMap<Vector2D, u32> = HashMap::new();
 
-            if current_pos == target_pos {
-                break;
+    for (idx, line) in lines.iter().enumerate() {
+        for other in lines[idx + 1..].iter() {
+            for point in line.get_lattice_intersections(other) {
+                *pair_counts.entry(point).or_insert(0) += 1;
             }
         }
     }
 
-    return area.iter().flatten().filter(|x| (**x) > 1).count();
+    // a point covered by k lines shows up in k * (k - 1) / 2 pairs
+    return pair_counts.into_iter().map(|(point, pairs)| {
+        let mut line_count = 2;
+        while line_count * (line_count - 1) / 2 < pairs {
+            line_count += 1;
+        }
+        (point, line_count)
+    }).collect();
+}
+
+/// Intersects every pair of lines, so memory only grows with the number of overlapping points.
+fn find_overlaps_sparse(lines: &[Line2D], threshold: OverlapThreshold) -> Vec<Vector2D> {
+    let coverage = match threshold.includes_single_lines() {
+        true => {
+            // every covered point is a candidate, so there's nothing to gain from intersecting
+            let mut coverage: HashMap<Vector2D, u32> = HashMap::new();
+            for point in lines.iter().flat_map(|line| line.get_points()) {
+                *coverage.entry(point).or_insert(0) += 1;
+            }
+            coverage
+        },
+        false => get_shared_points(lines),
+    };
+
+    let mut points: Vec<Vector2D> = coverage
+        .into_iter()
+        .filter(|(_, count)| threshold.matches(*count))
+        .map(|(point, _)| point)
+        .collect();
+
+    points.sort_by_key(|point| (point.x, point.y));
+
+    return points;
 }
 
 #[cfg(test)]
@@ -185,7 +385,7 @@ fn test_example_input_aa() {
 
     let axis_aligned_lines: Vec<Line2D> = lines.into_iter().filter(|line| line.from.x == line.to.x || line.from.y == line.to.y).collect();
 
-    let isects = calculate_intersections(axis_aligned_lines);
+    let isects = find_overlaps(&axis_aligned_lines, OverlapThreshold::AtLeast(2)).len();
 
     assert_eq!(isects, 5);
 }
@@ -198,7 +398,7 @@ fn test_example_input_with_diagonals_aa() {
         .map(|x| x.try_into().unwrap())
         .collect();
 
-    let isects = calculate_intersections(lines);
+    let isects = find_overlaps(&lines, OverlapThreshold::AtLeast(2)).len();
 
     assert_eq!(isects, 12);
 }
@@ -208,7 +408,7 @@ fn test_simple_aa() {
     let l1: Line2D = "-1,0 -> 1,0".try_into().unwrap();
     let l2: Line2D = "0,-1 -> 0,1".try_into().unwrap();
 
-    let isects = calculate_intersections(vec![l1, l2]);
+    let isects = find_overlaps(&[l1, l2], OverlapThreshold::AtLeast(2)).len();
     assert_eq!(isects, 1);
 }
 
@@ -217,7 +417,7 @@ fn test_overlapping_aa() {
     let l1: Line2D = "-1,0 -> 1,0".try_into().unwrap();
     let l2: Line2D = "-1,0 -> 1,0".try_into().unwrap();
 
-    let isects = calculate_intersections(vec![l1, l2]);
+    let isects = find_overlaps(&[l1, l2], OverlapThreshold::AtLeast(2)).len();
     assert_eq!(isects, 3);
 }
 
@@ -225,12 +425,18 @@ fn test_overlapping_aa() {
 fn test_step_vector_calculation() {
     let lines: Vec<Line2D> = vec![
         "-5,-5 -> 5,5".try_into().unwrap(),
-        "100,0 -> 0,0".try_into().unwrap()
+        "100,0 -> 0,0".try_into().unwrap(),
+        "0,0 -> 4,2".try_into().unwrap(),
+        "3,9 -> 0,0".try_into().unwrap(),
+        "7,7 -> 7,7".try_into().unwrap()
     ];
 
     let expected: Vec<Vector2D> = vec![
         Vector2D::new(1,1),
-        Vector2D::new(-1,0)
+        Vector2D::new(-1,0),
+        Vector2D::new(2,1),
+        Vector2D::new(-1,-3),
+        Vector2D::new(0,0)
     ];
 
     for (line, expected) in lines.into_iter().zip(expected) {
@@ -246,4 +452,272 @@ fn test_parsing() {
     assert_eq!(res.from.y, -2);
     assert_eq!(res.to.x, -3);
     assert_eq!(res.to.y, -4);
-}
\ No newline at end of file
+}
+
+// collinear overlaps in all directions, crossings off the lattice, single points and negative coordinates
+#[cfg(test)]
+const SCATTERED_FIELDS: [&str; 6] = [
+r"0,0 -> 10,0
+3,0 -> 14,0
+12,0 -> 6,0
+5,-5 -> 5,5",
+r"-6,-6 -> 6,6
+4,4 -> -2,-2
+-6,6 -> 6,-6
+0,-7 -> 0,7
+-7,0 -> 7,0",
+r"0,0 -> 1,1
+0,1 -> 1,0
+2,2 -> 2,2
+2,2 -> 4,4
+2,0 -> 2,9",
+r"0,0 -> 12,4
+3,1 -> 9,3
+0,4 -> 12,0
+6,-3 -> 6,9
+-3,2 -> 15,2",
+r"1,1 -> 1,1
+1,1 -> 1,1
+1,1 -> 3,1
+-1,3 -> 3,-1
+3,3 -> -1,-1",
+r"-10,-4 -> 10,6
+10,6 -> -10,-4
+-10,6 -> 10,-4
+0,-9 -> 0,9
+-8,1 -> 8,1
+4,-9 -> -4,7",
+];
+
+#[test]
+fn test_sparse_matches_dense() {
+    for field in SCATTERED_FIELDS {
+        let lines: Vec<Line2D> = field.split('\n').map(|x| x.try_into().unwrap()).collect();
+
+        for threshold in [OverlapThreshold::AtLeast(2), OverlapThreshold::AtLeast(3), OverlapThreshold::Exactly(2)] {
+            assert_eq!(find_overlaps_sparse(&lines, threshold), find_overlaps_dense(&lines, threshold), "{:?} in\n{}", threshold, field);
+        }
+    }
+}
+
+#[test]
+fn test_sparse_example_input() {
+    let lines: Vec<Line2D> = EXAMPLE_INPUT
+        .split('\n')
+        .filter(|x| !x.is_empty())
+        .map(|x| x.try_into().unwrap())
+        .collect();
+
+    assert_eq!(find_overlaps_sparse(&lines, OverlapThreshold::AtLeast(2)).len(), 12);
+}
+
+#[test]
+fn test_huge_coordinates() {
+    let lines: Vec<Line2D> = vec![
+        "-4000000,0 -> 4000000,0".try_into().unwrap(),
+        "0,-4000000 -> 0,4000000".try_into().unwrap(),
+        "-3000000,-3000000 -> 3000000,3000000".try_into().unwrap(),
+        "3999990,0 -> 4000010,0".try_into().unwrap(),
+    ];
+
+    // (0,0) shared by three lines, plus 11 points of the collinear overlap
+    assert_eq!(find_overlaps(&lines, OverlapThreshold::AtLeast(2)).len(), 12);
+
+    // both halves together span more than i32 can hold
+    let lines: Vec<Line2D> = vec![
+        "-2000000000,0 -> 0,0".try_into().unwrap(),
+        "0,0 -> 2000000000,0".try_into().unwrap(),
+        "1999999999,-5 -> 1999999999,5".try_into().unwrap(),
+    ];
+    assert_eq!(find_overlaps(&lines, OverlapThreshold::AtLeast(2)), vec![Vector2D::zero(), Vector2D::new(1999999999, 0)]);
+}
+
+#[test]
+fn test_single_point_line() {
+    let l1: Line2D = "2,2 -> 2,2".try_into().unwrap();
+    let l2: Line2D = "0,0 -> 4,4".try_into().unwrap();
+
+    assert_eq!(find_overlaps_dense(std::slice::from_ref(&l1), OverlapThreshold::AtLeast(2)).len(), 0);
+    assert_eq!(find_overlaps_sparse(&[l1.clone(), l2.clone()], OverlapThreshold::AtLeast(2)).len(), 1);
+    assert_eq!(find_overlaps_dense(&[l1, l2], OverlapThreshold::AtLeast(2)).len(), 1);
+}
+
+#[test]
+fn test_arbitrary_slopes() {
+    // 0,0 -> 4,2 covers (0,0), (2,1), (4,2); 0,1 -> 4,1 only crosses it in (2,1)
+    let l1: Line2D = "0,0 -> 4,2".try_into().unwrap();
+    let l2: Line2D = "0,1 -> 4,1".try_into().unwrap();
+    let l3: Line2D = "6,3 -> -2,-1".try_into().unwrap();
+
+    assert_eq!(l1.get_step_count(), 2);
+    assert_eq!(find_overlaps_dense(&[l1.clone(), l2.clone()], OverlapThreshold::AtLeast(2)).len(), 1);
+    assert_eq!(find_overlaps_sparse(&[l1.clone(), l2.clone()], OverlapThreshold::AtLeast(2)).len(), 1);
+
+    // lines crossing between lattice points don't overlap
+    let l4: Line2D = "0,1 -> 1,0".try_into().unwrap();
+    let l5: Line2D = "0,0 -> 1,1".try_into().unwrap();
+    assert_eq!(find_overlaps_sparse(&[l4, l5], OverlapThreshold::AtLeast(2)).len(), 0);
+
+    // collinear overlap of (0,0), (2,1), (4,2)
+    assert_eq!(find_overlaps_dense(&[l1.clone(), l3.clone()], OverlapThreshold::AtLeast(2)).len(), 3);
+    assert_eq!(find_overlaps_sparse(&[l1, l3], OverlapThreshold::AtLeast(2)).len(), 3);
+}
+
+#[cfg(test)]
+const EXAMPLE_DIAGRAM: &str = r"1.1....11.
+.111...2..
+..2.1.111.
+...1.2.2..
+.112313211
+...1.2....
+..1...1...
+.1.....1..
+1.......1.
+222111....
+";
+
+#[test]
+fn test_render_example() {
+    let lines: Vec<Line2D> = EXAMPLE_INPUT
+        .split('\n')
+        .filter(|x| !x.is_empty())
+        .map(|x| x.try_into().unwrap())
+        .collect();
+
+    assert_eq!(OverlapGrid::new(&lines, 1).to_string(), EXAMPLE_DIAGRAM);
+}
+
+#[test]
+fn test_heatmap_export() {
+    let lines: Vec<Line2D> = vec![
+        "0,0 -> 2999,0".try_into().unwrap(),
+        "0,0 -> 0,1999".try_into().unwrap(),
+    ];
+
+    let grid = OverlapGrid::with_max_dimension(&lines, 100);
+    assert_eq!(grid.get_width(), 100);
+    assert_eq!(grid.get_height(), 67);
+
+    let image = grid.to_pgm();
+    let header = b"P5\n100 67\n255\n";
+    assert_eq!(&image[..header.len()], header);
+    assert_eq!(image.len(), header.len() + 100 * 67);
+    // only the shared corner point is covered by both lines, the rest of each line by one
+    assert_eq!(image[header.len()], 255);
+    assert_eq!(image[header.len() + 1], 127);
+    assert_eq!(image[header.len() + 100], 127);
+    assert_eq!(image[header.len() + 101], 0);
+}
+
+#[test]
+fn test_heatmap_cells_match_points() {
+    for field in SCATTERED_FIELDS {
+        let lines: Vec<Line2D> = field.split('\n').map(|x| x.try_into().unwrap()).collect();
+        let points = OverlapGrid::new(&lines, 1);
+
+        for cell_size in 2..=5 {
+            let grid = OverlapGrid::new(&lines, cell_size);
+
+            let mut expected = vec![vec![0; grid.get_height()]; grid.get_width()];
+            for (x, column) in points.cells.iter().enumerate() {
+                for (y, count) in column.iter().enumerate() {
+                    let cell = &mut expected[x / cell_size as usize][y / cell_size as usize];
+                    *cell = max(*cell, *count);
+                }
+            }
+
+            assert_eq!(grid.cells, expected, "cell size {} in\n{}", cell_size, field);
+        }
+    }
+}
+
+#[test]
+fn test_heatmap_of_huge_field() {
+    let lines: Vec<Line2D> = vec![
+        "-2000000000,0 -> 0,0".try_into().unwrap(),
+        "0,0 -> 2000000000,0".try_into().unwrap(),
+    ];
+
+    let grid = OverlapGrid::with_max_dimension(&lines, 100);
+    assert_eq!((grid.get_width(), grid.get_height()), (100, 1));
+    assert_eq!(grid.cells.iter().filter(|column| column[0] == 2).count(), 1);
+    assert!(grid.cells.iter().all(|column| column[0] >= 1));
+
+    // a single cell wider than i32
+    let grid = OverlapGrid::with_max_dimension(&lines, 1);
+    assert_eq!(grid.cells, vec![vec![2]]);
+    assert_eq!(grid.get_points(OverlapThreshold::AtLeast(2)), vec![Vector2D::new(-2000000000, 0)]);
+}
+
+#[test]
+fn test_thresholds() {
+    let lines: Vec<Line2D> = EXAMPLE_INPUT
+        .split('\n')
+        .filter(|x| !x.is_empty())
+        .map(|x| x.try_into().unwrap())
+        .collect();
+
+    // counts taken from the example diagram
+    let expected = [
+        (OverlapThreshold::AtLeast(1), 39),
+        (OverlapThreshold::AtLeast(2), 12),
+        (OverlapThreshold::AtLeast(3), 2),
+        (OverlapThreshold::Exactly(1), 27),
+        (OverlapThreshold::Exactly(2), 10),
+        (OverlapThreshold::Exactly(3), 2),
+        (OverlapThreshold::Exactly(4), 0),
+    ];
+
+    for (threshold, count) in expected {
+        let dense = find_overlaps_dense(&lines, threshold);
+        let sparse = find_overlaps_sparse(&lines, threshold);
+
+        assert_eq!(dense.len(), count, "{:?}", threshold);
+        assert_eq!(dense, sparse, "{:?}", threshold);
+    }
+
+    assert_eq!(find_overlaps(&lines, OverlapThreshold::AtLeast(3)), vec![Vector2D::new(4, 4), Vector2D::new(6, 4)]);
+
+    assert_eq!(OverlapThreshold::parse("3", true).unwrap(), OverlapThreshold::Exactly(3));
+    assert!(OverlapThreshold::parse("0", true).is_err());
+    assert!(OverlapThreshold::parse("0", false).is_err());
+}
+
+#[test]
+fn test_thresholds_on_large_field() {
+    let lines: Vec<Line2D> = vec![
+        "0,0 -> 2999,0".try_into().unwrap(),
+        "0,0 -> 0,2999".try_into().unwrap(),
+        "0,0 -> 2999,2999".try_into().unwrap(),
+        "1500,0 -> 1500,2999".try_into().unwrap(),
+        "0,1500 -> 2999,1500".try_into().unwrap(),
+        "100,0 -> 200,0".try_into().unwrap(),
+        "0,2999 -> 2999,0".try_into().unwrap(),
+    ];
+
+    // big enough for find_overlaps to go the pairwise route
+    assert!(get_bounds(&lines).get_area() > DENSE_AREA_LIMIT);
+
+    for count in 1..=4 {
+        for threshold in [OverlapThreshold::AtLeast(count), OverlapThreshold::Exactly(count)] {
+            assert_eq!(find_overlaps(&lines, threshold), find_overlaps_dense(&lines, threshold), "{:?}", threshold);
+        }
+    }
+}
+
+#[test]
+fn test_line_classes() {
+    let lines: Vec<Line2D> = vec![
+        "0,9 -> 5,9".try_into().unwrap(),
+        "7,0 -> 7,4".try_into().unwrap(),
+        "8,0 -> 0,8".try_into().unwrap(),
+        "0,0 -> 4,2".try_into().unwrap(),
+    ];
+
+    let classes: Vec<LineClass> = lines.iter().map(|x| x.get_class()).collect();
+    assert_eq!(classes, vec![LineClass::Horizontal, LineClass::Vertical, LineClass::Diagonal, LineClass::Arbitrary]);
+
+    let parsed: LineClass = "diagonal".try_into().unwrap();
+    assert_eq!(parsed, LineClass::Diagonal);
+    assert!(LineClass::try_from("curved").is_err());
+}
@@ -1,49 +1,374 @@
+#![allow(clippy::needless_return)]
+
 extern crate clap;
 
-use std::error::Error;
+use std::{cmp::max, error::Error};
 
 use clap::{App, Arg};
+use num_bigint::BigUint;
 
-struct FishDay {
-    fishes_in_day: [u64; 9],
+/// Reproduction cycle of a species. The population state and the daily step are derived from it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct LifecycleModel {
+    /// Timer of a fish right after it gave birth.
+    reset_timer: usize,
+    /// Timer of a newborn fish.
+    newborn_timer: usize,
+    /// Newborns per fish and birth.
+    litter_size: u64,
+    /// Days after which a fish dies, counted from its birth or the start of the simulation.
+    max_age: Option<usize>,
 }
 
-impl FishDay {
-    fn get_next_day(&self) -> Self {
-        let reproducing_fish = self.fishes_in_day[0];
+impl LifecycleModel {
+    fn lanternfish() -> Self {
+        return LifecycleModel {
+            reset_timer: 6,
+            newborn_timer: 8,
+            litter_size: 1,
+            max_age: None
+        };
+    }
+
+    fn get_timer_count(&self) -> usize {
+        return max(self.reset_timer, self.newborn_timer) + 1;
+    }
 
-        let mut next_day = [0u64; 9];
-        next_day[..8].clone_from_slice(&self.fishes_in_day[1..]);
-        next_day[8] = reproducing_fish;
-        next_day[6] += reproducing_fish;
+    /// Fish are only told apart by age if they can die, otherwise the timer is the whole state.
+    fn get_state_count(&self) -> usize {
+        return self.get_timer_count() * self.max_age.unwrap_or(1);
+    }
 
-        return FishDay {
-            fishes_in_day: next_day
-        };
+    fn get_state_index(&self, timer: usize, age: usize) -> usize {
+        return age * self.get_timer_count() + timer;
     }
 
-    fn get_living_fish_count(&self) -> u64 {
-        return self.fishes_in_day.iter().sum();
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.max_age == Some(0) {
+            return Err("Maximum age has to be at least 1 day".into());
+        }
+
+        return Ok(());
     }
 }
 
-impl TryFrom<&str> for FishDay {
-    type Error = Box<dyn Error>;
+struct FishDay {
+    // indexed by LifecycleModel::get_state_index
+    fishes_in_day: Vec<u64>,
+    model: LifecycleModel,
+}
 
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let mut day = FishDay {
-            fishes_in_day: [0u64; 9]
+impl FishDay {
+    fn new(model: LifecycleModel) -> Self {
+        return FishDay {
+            fishes_in_day: vec![0u64; model.get_state_count()],
+            model
         };
+    }
+
+    /// Parses the comma separated timers of the initial fish, all of them aged 0.
+    fn parse(value: &str, model: LifecycleModel) -> Result<Self, Box<dyn Error>> {
+        model.validate()?;
+
+        let mut day = FishDay::new(model);
 
         for parsed_day in value.split(',').map(|x| x.parse::<usize>()) {
             match parsed_day {
-                Ok(fish_in_day) => day.fishes_in_day[fish_in_day] += 1,
+                Ok(timer) if timer < day.model.get_timer_count() => {
+                    let index = day.model.get_state_index(timer, 0);
+                    day.fishes_in_day[index] += 1;
+                },
+                Ok(timer) => return Err(format!("Timer {} exceeds the timers of the lifecycle model", timer).into()),
                 Err(e) => return Err(e.into()),
             }
         }
 
         Ok(day)
     }
+
+    fn get_next_day(&self) -> Self {
+        let model = &self.model;
+        let mut next_day = FishDay::new(model.clone());
+
+        for age in 0..model.max_age.unwrap_or(1) {
+            // without a maximum age everything stays in the single age 0 bucket
+            let next_age = match model.max_age {
+                Some(_) => age + 1,
+                None => 0,
+            };
+            let survives = model.max_age.is_none_or(|max_age| next_age < max_age);
+
+            for timer in 0..model.get_timer_count() {
+                let fish = self.fishes_in_day[model.get_state_index(timer, age)];
+
+                if timer == 0 {
+                    next_day.fishes_in_day[model.get_state_index(model.newborn_timer, 0)] += fish * model.litter_size;
+                }
+
+                if survives {
+                    let next_timer = if timer == 0 { model.reset_timer } else { timer - 1 };
+                    next_day.fishes_in_day[model.get_state_index(next_timer, next_age)] += fish;
+                }
+            }
+        }
+
+        return next_day;
+    }
+}
+
+/// Linear map taking the fish counts of one day to the next, indexed [to][from].
+struct TransitionMatrix {
+    entries: Vec<Vec<BigUint>>,
+}
+
+impl TransitionMatrix {
+    /// Derives the matrix from a single day's step by stepping each state bucket on its own.
+    fn from_model(model: &LifecycleModel) -> Self {
+        let state_count = model.get_state_count();
+        let mut entries = vec![vec![BigUint::from(0u8); state_count]; state_count];
+
+        for (to, from, factor) in TransitionMatrix::get_sparse_entries(model) {
+            entries[to][from] = BigUint::from(factor);
+        }
+
+        return TransitionMatrix {
+            entries
+        };
+    }
+
+    /// Nonzero entries as (to, from, factor), a fish ends up in at most two states the next day.
+    fn get_sparse_entries(model: &LifecycleModel) -> Vec<(usize, usize, u64)> {
+        let mut entries = Vec::new();
+
+        for from in 0..model.get_state_count() {
+            let mut unit_day = FishDay::new(model.clone());
+            unit_day.fishes_in_day[from] = 1;
+
+            for (to, factor) in unit_day.get_next_day().fishes_in_day.into_iter().enumerate() {
+                if factor != 0 {
+                    entries.push((to, from, factor));
+                }
+            }
+        }
+
+        return entries;
+    }
+
+    fn identity(size: usize) -> Self {
+        let mut entries = vec![vec![BigUint::from(0u8); size]; size];
+
+        for (i, row) in entries.iter_mut().enumerate() {
+            row[i] = BigUint::from(1u8);
+        }
+
+        return TransitionMatrix {
+            entries
+        };
+    }
+
+    fn multiply(&self, other: &TransitionMatrix, modulus: Option<&BigUint>) -> Self {
+        let size = self.entries.len();
+        let mut entries = vec![vec![BigUint::from(0u8); size]; size];
+
+        for (row, result_row) in entries.iter_mut().enumerate() {
+            for (col, result) in result_row.iter_mut().enumerate() {
+                for k in 0..size {
+                    *result += &self.entries[row][k] * &other.entries[k][col];
+                }
+
+                if let Some(m) = modulus {
+                    *result %= m;
+                }
+            }
+        }
+
+        return TransitionMatrix {
+            entries
+        };
+    }
+
+    /// Exponentiation by squaring, so simulating n days takes O(log n) multiplications.
+    fn pow(&self, mut exponent: u64, modulus: Option<&BigUint>) -> Self {
+        let mut result = TransitionMatrix::identity(self.entries.len());
+        // multiplying by the identity copies the matrix and reduces it
+        let mut base = self.multiply(&TransitionMatrix::identity(self.entries.len()), modulus);
+
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.multiply(&base, modulus);
+            }
+
+            base = base.multiply(&base, modulus);
+            exponent >>= 1;
+        }
+
+        return result;
+    }
+
+    fn apply(&self, day: &FishDay, modulus: Option<&BigUint>) -> Vec<BigUint> {
+        let mut counts: Vec<BigUint> = self.entries
+            .iter()
+            .map(|row| row.iter().zip(day.fishes_in_day.iter()).map(|(factor, count)| factor * count).sum())
+            .collect();
+
+        if let Some(m) = modulus {
+            counts.iter_mut().for_each(|count| *count %= m);
+        }
+
+        return counts;
+    }
+}
+
+/// Fish counts of the next day, from the entries of `TransitionMatrix::get_sparse_entries`.
+fn step_counts(entries: &[(usize, usize, u64)], counts: &[BigUint], modulus: Option<&BigUint>) -> Vec<BigUint> {
+    let mut next_counts = vec![BigUint::from(0u8); counts.len()];
+
+    for (to, from, factor) in entries.iter() {
+        next_counts[*to] += &counts[*from] * *factor;
+    }
+
+    if let Some(m) = modulus {
+        next_counts.iter_mut().for_each(|count| *count %= m);
+    }
+
+    return next_counts;
+}
+
+/// Population of every day up to `days` as CSV: day, total and one column per timer, summed over all ages.
+fn get_population_csv(day: &FishDay, days: u64) -> String {
+    let model = &day.model;
+    let entries = TransitionMatrix::get_sparse_entries(model);
+
+    let mut csv = String::from("day,total");
+    for timer in 0..model.get_timer_count() {
+        csv.push_str(&format!(",timer_{}", timer));
+    }
+    csv.push('\n');
+
+    let mut counts: Vec<BigUint> = day.fishes_in_day.iter().map(|count| BigUint::from(*count)).collect();
+
+    for elapsed in 0..=days {
+        let mut per_timer = vec![BigUint::from(0u8); model.get_timer_count()];
+        for (state, count) in counts.iter().enumerate() {
+            per_timer[state % model.get_timer_count()] += count;
+        }
+
+        let total: BigUint = per_timer.iter().sum();
+        csv.push_str(&format!("{},{}", elapsed, total));
+        for count in per_timer {
+            csv.push_str(&format!(",{}", count));
+        }
+        csv.push('\n');
+
+        counts = step_counts(&entries, &counts, None);
+    }
+
+    return csv;
+}
+
+/// Asymptotic factor by which the population grows each day, i.e. the dominant eigenvalue of the transition.
+fn estimate_growth_rate(model: &LifecycleModel) -> f64 {
+    const ITERATIONS: usize = 2000;
+
+    let entries = TransitionMatrix::get_sparse_entries(model);
+
+    // power iteration; averaging the log growth over the second half also copes with periodic models
+    let mut vector = vec![1f64; model.get_state_count()];
+    let mut log_growth_sum = 0f64;
+
+    for iteration in 0..ITERATIONS {
+        let mut next = vec![0f64; vector.len()];
+        for (to, from, factor) in entries.iter() {
+            next[*to] += *factor as f64 * vector[*from];
+        }
+
+        let norm: f64 = next.iter().sum();
+
+        if norm == 0f64 {
+            // the population dies out
+            return 0f64;
+        }
+
+        if iteration >= ITERATIONS / 2 {
+            log_growth_sum += norm.ln();
+        }
+
+        vector = next.iter().map(|x| x / norm).collect();
+    }
+
+    return (log_growth_sum / (ITERATIONS - ITERATIONS / 2) as f64).exp();
+}
+
+/// Days it takes the population to double at `growth_rate`, `None` if it never does.
+fn get_doubling_time(growth_rate: f64) -> Option<f64> {
+    if growth_rate <= 1f64 {
+        return None;
+    }
+
+    return Some(2f64.ln() / growth_rate.ln());
+}
+
+fn parse_modulus(value: &str) -> Result<u64, Box<dyn Error>> {
+    let modulus: u64 = value.parse()?;
+
+    if modulus == 0 {
+        return Err("Modulus has to be at least 1".into());
+    }
+
+    return Ok(modulus);
+}
+
+/// Living fish after `days`, exact or reduced modulo `modulus` when given.
+fn simulate_days(day: &FishDay, days: u64, modulus: Option<u64>) -> BigUint {
+    let state_count = day.model.get_state_count() as u64;
+
+    // a day's step touches each of the few nonzero entries per state, a matrix product all state_count³ entry pairs
+    let step_cost = days.saturating_mul(state_count);
+    let matrix_cost = state_count.saturating_pow(3).saturating_mul(u64::from(64 - days.leading_zeros()));
+
+    return match step_cost <= matrix_cost {
+        true => simulate_days_stepwise(day, days, modulus),
+        false => simulate_days_by_matrix(day, days, modulus),
+    };
+}
+
+fn simulate_days_stepwise(day: &FishDay, days: u64, modulus: Option<u64>) -> BigUint {
+    let modulus = modulus.map(BigUint::from);
+    let entries = TransitionMatrix::get_sparse_entries(&day.model);
+
+    let mut counts: Vec<BigUint> = day.fishes_in_day.iter().map(|count| BigUint::from(*count)).collect();
+
+    for _ in 0..days {
+        counts = step_counts(&entries, &counts, modulus.as_ref());
+    }
+
+    let total: BigUint = counts.into_iter().sum();
+
+    return match modulus {
+        Some(m) => total % m,
+        None => total,
+    };
+}
+
+fn simulate_days_by_matrix(day: &FishDay, days: u64, modulus: Option<u64>) -> BigUint {
+    let modulus = modulus.map(BigUint::from);
+    let transition = TransitionMatrix::from_model(&day.model);
+
+    let counts = transition.pow(days, modulus.as_ref()).apply(day, modulus.as_ref());
+    let total: BigUint = counts.into_iter().sum();
+
+    return match modulus {
+        Some(m) => total % m,
+        None => total,
+    };
+}
+
+impl TryFrom<&str> for FishDay {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        return FishDay::parse(value, LifecycleModel::lanternfish());
+    }
 }
 
 fn main() {
@@ -56,28 +381,74 @@ fn main() {
                         .help("Days to simulate.")
                         .required(true)
                         .index(2))
+                    .arg(Arg::with_name("MODULO")
+                        .help("Report the fish count modulo this number, for day counts too large to count exactly.")
+                        .long("modulo")
+                        .takes_value(true))
+                    .arg(Arg::with_name("RESET_TIMER")
+                        .help("Timer of a fish after it gave birth.")
+                        .long("reset-timer")
+                        .takes_value(true)
+                        .default_value("6"))
+                    .arg(Arg::with_name("NEWBORN_TIMER")
+                        .help("Timer of a newborn fish.")
+                        .long("newborn-timer")
+                        .takes_value(true)
+                        .default_value("8"))
+                    .arg(Arg::with_name("LITTER_SIZE")
+                        .help("Number of fish born at once.")
+                        .long("litter-size")
+                        .takes_value(true)
+                        .default_value("1"))
+                    .arg(Arg::with_name("MAX_AGE")
+                        .help("Days after which a fish dies, fish live forever if omitted.")
+                        .long("max-age")
+                        .takes_value(true))
+                    .arg(Arg::with_name("CSV")
+                        .help("Print the population of every simulated day as CSV instead.")
+                        .long("csv"))
+                    .arg(Arg::with_name("GROWTH")
+                        .help("Report the asymptotic daily growth rate and doubling time.")
+                        .long("growth"))
                     .get_matches();
 
     let input_file_path = commandline_matches.value_of("INPUT").unwrap();
-    let days_to_simulate = commandline_matches.value_of("DAYS").unwrap().parse::<usize>().unwrap();
+    let days_to_simulate = commandline_matches.value_of("DAYS").unwrap().parse::<u64>().unwrap();
+    let modulus = commandline_matches.value_of("MODULO").map(|x| parse_modulus(x).unwrap());
 
     let file_contents = std::fs::read_to_string(input_file_path)
         .expect("Failed to open the readings file");
 
-    let mut fish_state: FishDay = file_contents
-        .split('\n')
-        .into_iter()
-        .next()
-        .unwrap()
-        .try_into()
-        .unwrap();
-    
-    
-    for _ in 0..days_to_simulate {
-        fish_state = fish_state.get_next_day();
+    let model = LifecycleModel {
+        reset_timer: commandline_matches.value_of("RESET_TIMER").unwrap().parse().unwrap(),
+        newborn_timer: commandline_matches.value_of("NEWBORN_TIMER").unwrap().parse().unwrap(),
+        litter_size: commandline_matches.value_of("LITTER_SIZE").unwrap().parse().unwrap(),
+        max_age: commandline_matches.value_of("MAX_AGE").map(|x| x.parse().unwrap()),
+    };
+
+    let fish_state = FishDay::parse(file_contents.split('\n').next().unwrap(), model).unwrap();
+
+    if commandline_matches.is_present("CSV") {
+        print!("{}", get_population_csv(&fish_state, days_to_simulate));
+        return;
+    }
+
+    if commandline_matches.is_present("GROWTH") {
+        let growth_rate = estimate_growth_rate(&fish_state.model);
+
+        println!("Asymptotic daily growth rate: {:.6}", growth_rate);
+        match get_doubling_time(growth_rate) {
+            Some(doubling_time) => println!("Doubling time: {:.2} days", doubling_time),
+            None => println!("Doubling time: population doesn't grow"),
+        }
     }
 
-    println!("Living fish after {} days: {}", days_to_simulate, fish_state.get_living_fish_count());
+    let living_fish = simulate_days(&fish_state, days_to_simulate, modulus);
+
+    match modulus {
+        Some(m) => println!("Living fish after {} days, modulo {}: {}", days_to_simulate, m, living_fish),
+        None => println!("Living fish after {} days: {}", days_to_simulate, living_fish),
+    }
 }
 
 #[cfg(test)]
@@ -103,4 +474,155 @@ fn test_sample_input() {
     }
 
     assert_eq!(days[18].fishes_in_day, expected.fishes_in_day);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_matrix_matches_iteration() {
+    let mut day: FishDay = TEST_INPUT.try_into().unwrap();
+    let start: FishDay = TEST_INPUT.try_into().unwrap();
+
+    for days in 0..=256u64 {
+        assert_eq!(simulate_days_by_matrix(&start, days, None), BigUint::from(day.fishes_in_day.iter().sum::<u64>()), "{} days", days);
+        assert_eq!(simulate_days_stepwise(&start, days, None), BigUint::from(day.fishes_in_day.iter().sum::<u64>()), "{} days", days);
+        day = day.get_next_day();
+    }
+}
+
+#[test]
+fn test_sample_totals() {
+    let day: FishDay = TEST_INPUT.try_into().unwrap();
+
+    assert_eq!(simulate_days(&day, 18, None), BigUint::from(26u64));
+    assert_eq!(simulate_days(&day, 80, None), BigUint::from(5934u64));
+    assert_eq!(simulate_days(&day, 256, None), BigUint::from(26984457539u64));
+}
+
+#[test]
+fn test_beyond_u64() {
+    let day: FishDay = TEST_INPUT.try_into().unwrap();
+
+    let exact = simulate_days(&day, 1000, None);
+    assert!(exact > BigUint::from(u64::MAX));
+    assert_eq!(simulate_days(&day, 1000, Some(1_000_000_007)), exact % BigUint::from(1_000_000_007u64));
+}
+
+#[test]
+fn test_billion_days_modulo() {
+    let day: FishDay = TEST_INPUT.try_into().unwrap();
+
+    // enough days for simulate_days to pick matrix powers, still few enough to step through
+    assert_eq!(simulate_days(&day, 100_000, Some(1_000_000_007)), simulate_days_stepwise(&day, 100_000, Some(1_000_000_007)));
+
+    assert_eq!(simulate_days(&day, 1_000_000_000, Some(1_000_000_007)), BigUint::from(249911329u64));
+}
+
+#[test]
+fn test_zero_modulus() {
+    assert!(parse_modulus("0").is_err());
+    assert_eq!(parse_modulus("1000000007").unwrap(), 1_000_000_007);
+}
+
+#[test]
+fn test_custom_lifecycle() {
+    // every fish splits into three each day: one parent and two newborns, all with timer 0
+    let model = LifecycleModel {
+        reset_timer: 0,
+        newborn_timer: 0,
+        litter_size: 2,
+        max_age: None
+    };
+
+    let day = FishDay::parse("0", model).unwrap();
+    assert_eq!(day.fishes_in_day, vec![1]);
+    assert_eq!(simulate_days(&day, 5, None), BigUint::from(243u64));
+}
+
+#[test]
+fn test_max_age() {
+    let model = LifecycleModel {
+        reset_timer: 1,
+        newborn_timer: 1,
+        litter_size: 1,
+        max_age: Some(3)
+    };
+
+    let mut days = vec![FishDay::parse("0", model).unwrap()];
+    for _ in 0..10 {
+        days.push(days.last().unwrap().get_next_day());
+    }
+
+    // each fish lives for three days and gives birth twice at most, which settles into a 2-cycle
+    let counts: Vec<u64> = days.iter().map(|x| x.fishes_in_day.iter().sum()).collect();
+    assert_eq!(counts, vec![1, 2, 2, 3, 2, 4, 2, 4, 2, 4, 2]);
+
+    for (elapsed, count) in counts.iter().enumerate() {
+        assert_eq!(simulate_days(&days[0], elapsed as u64, None), BigUint::from(*count));
+    }
+}
+
+#[test]
+fn test_stepwise_matches_matrix_modulo() {
+    let day: FishDay = TEST_INPUT.try_into().unwrap();
+
+    for days in [0u64, 1, 80, 1000] {
+        assert_eq!(simulate_days_stepwise(&day, days, Some(1_000_000_007)), simulate_days_by_matrix(&day, days, Some(1_000_000_007)));
+    }
+}
+
+#[test]
+fn test_zero_max_age() {
+    let model = LifecycleModel {
+        reset_timer: 6,
+        newborn_timer: 8,
+        litter_size: 1,
+        max_age: Some(0)
+    };
+
+    assert!(FishDay::parse("3,4", model).is_err());
+}
+
+#[test]
+fn test_timer_out_of_range() {
+    assert!(FishDay::try_from("3,9").is_err());
+}
+
+#[test]
+fn test_population_csv() {
+    let day: FishDay = TEST_INPUT.try_into().unwrap();
+    let csv = get_population_csv(&day, 18);
+    let lines: Vec<&str> = csv.lines().collect();
+
+    assert_eq!(lines.len(), 20);
+    assert_eq!(lines[0], "day,total,timer_0,timer_1,timer_2,timer_3,timer_4,timer_5,timer_6,timer_7,timer_8");
+    assert_eq!(lines[1], "0,5,0,1,1,2,1,0,0,0,0");
+    assert_eq!(lines[2], "1,5,1,1,2,1,0,0,0,0,0");
+    assert_eq!(lines[19], "18,26,3,5,3,2,2,1,5,1,4");
+}
+
+#[test]
+fn test_growth_rate() {
+    let growth_rate = estimate_growth_rate(&LifecycleModel::lanternfish());
+
+    // dominant root of x^9 = x^2 + 1
+    assert!((growth_rate.powi(9) - growth_rate.powi(2) - 1f64).abs() < 1e-9);
+    assert!((get_doubling_time(growth_rate).unwrap() - 2f64.ln() / growth_rate.ln()).abs() < 1e-12);
+
+    let day: FishDay = TEST_INPUT.try_into().unwrap();
+    let ratio = simulate_days(&day, 3001, None).to_string().parse::<f64>().unwrap()
+        / simulate_days(&day, 3000, None).to_string().parse::<f64>().unwrap();
+    assert!((ratio - growth_rate).abs() < 1e-6);
+}
+
+#[test]
+fn test_growth_rate_of_stable_population() {
+    let model = LifecycleModel {
+        reset_timer: 1,
+        newborn_timer: 1,
+        litter_size: 1,
+        max_age: Some(3)
+    };
+
+    let growth_rate = estimate_growth_rate(&model);
+    assert!((growth_rate - 1f64).abs() < 1e-6);
+    assert_eq!(get_doubling_time(0.5), None);
+}
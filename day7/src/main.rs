@@ -1,4 +1,6 @@
-use std::{error::Error};
+#![allow(clippy::needless_return)]
+
+use std::{error::Error, ops::RangeInclusive};
 
 use clap::{App, Arg};
 
@@ -8,17 +10,33 @@ struct CrabArea {
     max_pos: i32,
 }
 
+#[derive(Debug, PartialEq, Eq)]
 struct BlastLocation {
     position: i32,
-    cost: i32,
+    cost: u128,
 }
 
 impl BlastLocation {
-    fn new(position: i32, cost: i32) -> Self {
+    fn new(position: i32, cost: u128) -> Self {
         return BlastLocation { position, cost }
     }
 }
 
+/// Meeting point of a group of crabs.
+#[derive(Debug, PartialEq, Eq)]
+struct BlastTarget {
+    position: i32,
+    /// Indices into the starting positions.
+    crabs: Vec<usize>,
+    cost: u128,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct MultiBlastLocation {
+    targets: Vec<BlastTarget>,
+    cost: u128,
+}
+
 impl TryFrom<&str> for CrabArea {
     type Error = Box<dyn Error>;
 
@@ -29,8 +47,8 @@ impl TryFrom<&str> for CrabArea {
         .map(|x| x.parse().unwrap())
         .collect();
 
-        let min_pos: i32 = starting_positions.iter().min().unwrap().clone();
-        let max_pos: i32 = starting_positions.iter().max().unwrap().clone();
+        let min_pos: i32 = *starting_positions.iter().min().unwrap();
+        let max_pos: i32 = *starting_positions.iter().max().unwrap();
 
         Ok(
             CrabArea {
@@ -42,13 +60,171 @@ impl TryFrom<&str> for CrabArea {
     }
 }
 
+/// Fuel a crab burns to move to the blast position.
+trait FuelCostModel {
+    /// Cost for the crab at `crab_index` in the input to move `distance` steps.
+    fn get_cost(&self, crab_index: usize, distance: u64) -> u128;
+
+    /// Whether the total cost is convex in the blast position, which lets the solver search
+    /// for the minimum instead of trying every position.
+    fn is_convex(&self) -> bool;
+}
+
+/// One unit of fuel per step.
+struct LinearCost;
+
+impl FuelCostModel for LinearCost {
+    fn get_cost(&self, _crab_index: usize, distance: u64) -> u128 {
+        return distance as u128;
+    }
+
+    fn is_convex(&self) -> bool {
+        return true;
+    }
+}
+
+/// Every step costs one more than the previous one.
+struct TriangularCost;
+
+impl FuelCostModel for TriangularCost {
+    fn get_cost(&self, _crab_index: usize, distance: u64) -> u128 {
+        // Gaussian Sum formula
+        let distance = distance as u128;
+        return (distance.pow(2) + distance) / 2;
+    }
+
+    fn is_convex(&self) -> bool {
+        return true;
+    }
+}
+
+/// Fuel grows with the square of the distance.
+struct QuadraticCost;
+
+impl FuelCostModel for QuadraticCost {
+    fn get_cost(&self, _crab_index: usize, distance: u64) -> u128 {
+        return (distance as u128).pow(2);
+    }
+
+    fn is_convex(&self) -> bool {
+        return true;
+    }
+}
+
+/// Limits the fuel of a single crab, e.g. for crabs that get towed beyond some point.
+struct CappedCost {
+    inner: Box<dyn FuelCostModel>,
+    cap: u128,
+}
+
+impl FuelCostModel for CappedCost {
+    fn get_cost(&self, crab_index: usize, distance: u64) -> u128 {
+        return self.inner.get_cost(crab_index, distance).min(self.cap);
+    }
+
+    fn is_convex(&self) -> bool {
+        // flattening a convex function at the top breaks convexity
+        return false;
+    }
+}
+
+/// Scales the cost of each crab by its own weight.
+struct WeightedCost {
+    inner: Box<dyn FuelCostModel>,
+    weights: Vec<u64>,
+}
+
+impl FuelCostModel for WeightedCost {
+    fn get_cost(&self, crab_index: usize, distance: u64) -> u128 {
+        return self.inner.get_cost(crab_index, distance) * self.weights[crab_index] as u128;
+    }
+
+    fn is_convex(&self) -> bool {
+        // non-negative weights keep a sum of convex functions convex
+        return self.inner.is_convex();
+    }
+}
+
+impl TryFrom<&str> for Box<dyn FuelCostModel> {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "linear" => Ok(Box::new(LinearCost)),
+            "triangular" => Ok(Box::new(TriangularCost)),
+            "quadratic" => Ok(Box::new(QuadraticCost)),
+            _ => Err(format!("Unknown cost model \"{}\"", value).into()),
+        }
+    }
+}
+
 impl CrabArea {
-    fn calculate_blast_position(&self,cost_function: impl Fn(i32) -> i32) -> BlastLocation {
+    fn get_fuel_cost(&self, blast_pos: i32, cost_model: &dyn FuelCostModel) -> u128 {
+        return self.starting_positions
+            .iter()
+            .enumerate()
+            .map(|(crab_index, crab_pos)| cost_model.get_cost(crab_index, (blast_pos as i64 - *crab_pos as i64).unsigned_abs()))
+            .sum();
+    }
+
+    /// Cost of every position between the outermost crabs.
+    fn get_cost_curve(&self, cost_model: &dyn FuelCostModel) -> Vec<(i32, u128)> {
+        return (self.min_pos..=self.max_pos)
+            .map(|blast_pos| (blast_pos, self.get_fuel_cost(blast_pos, cost_model)))
+            .collect();
+    }
+
+    /// Runs of positions costing as much as `blast`, which has to be the leftmost minimum.
+    fn get_tied_positions(&self, blast: &BlastLocation, cost_model: &dyn FuelCostModel) -> Vec<RangeInclusive<i32>> {
+        if cost_model.is_convex() {
+            // the minima of a convex function are contiguous, so only the right end of the run has to be searched
+            let (mut last, mut upper) = (blast.position, self.max_pos);
+
+            while last < upper {
+                let middle = (last as i64 + (upper as i64 - last as i64 + 1) / 2) as i32;
+
+                if self.get_fuel_cost(middle, cost_model) == blast.cost {
+                    last = middle;
+                } else {
+                    upper = middle - 1;
+                }
+            }
+
+            return vec![blast.position..=last];
+        }
+
+        let mut runs: Vec<RangeInclusive<i32>> = vec![];
+
+        for (blast_pos, cost) in self.get_cost_curve(cost_model) {
+            if cost != blast.cost {
+                continue;
+            }
+
+            match runs.last_mut() {
+                Some(run) if *run.end() + 1 == blast_pos => *run = *run.start()..=blast_pos,
+                _ => runs.push(blast_pos..=blast_pos),
+            }
+        }
+
+        return runs;
+    }
+
+    /// Searches for the minimum if the model is convex and tries every position otherwise.
+    fn calculate_blast_position(&self, cost_model: &dyn FuelCostModel) -> BlastLocation {
+        if cost_model.is_convex() {
+            return self.calculate_blast_position_convex(cost_model);
+        } else {
+            return self.calculate_blast_position_brute_force(cost_model);
+        }
+    }
+
+    /// Tries every position between the outermost crabs, ties go to the leftmost position.
+    fn calculate_blast_position_brute_force(&self, cost_model: &dyn FuelCostModel) -> BlastLocation {
         let mut best_blast_pos = self.min_pos;
-        let mut best_cost = i32::MAX;
+        let mut best_cost = u128::MAX;
 
-        for blast_pos in self.min_pos..self.max_pos {
-            let fuel_cost: i32 = self.starting_positions.iter().map(|crab_pos| cost_function((blast_pos - crab_pos).abs())).sum();
+        for blast_pos in self.min_pos..=self.max_pos {
+            let fuel_cost = self.get_fuel_cost(blast_pos, cost_model);
 
             if fuel_cost < best_cost {
                 best_cost = fuel_cost;
@@ -58,15 +234,129 @@ impl CrabArea {
 
         return BlastLocation::new(best_blast_pos, best_cost);
     }
-}
 
-fn constant_fuel_cost_function(distance: i32) -> i32 {
-    return distance;
-}
+    /// Binary search for the leftmost position where the cost stops falling, only valid for
+    /// convex cost models.
+    fn calculate_blast_position_convex(&self, cost_model: &dyn FuelCostModel) -> BlastLocation {
+        let mut low = self.min_pos;
+        let mut high = self.max_pos;
+
+        while low < high {
+            let mid = (low as i64 + high as i64).div_euclid(2) as i32;
+
+            if self.get_fuel_cost(mid, cost_model) <= self.get_fuel_cost(mid + 1, cost_model) {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+
+        return BlastLocation::new(low, self.get_fuel_cost(low, cost_model));
+    }
+
+    /// Splits the crabs between up to `k` blast positions with linear fuel burn (1-D k-median).
+    ///
+    /// Optimal groups are contiguous in sorted order, so dynamic programming over the sorted
+    /// positions finds the best split in O(k n²), with each group's cost taken from prefix sums.
+    fn calculate_blast_positions(&self, k: usize) -> MultiBlastLocation {
+        let mut order: Vec<usize> = (0..self.starting_positions.len()).collect();
+        order.sort_by_key(|crab| self.starting_positions[*crab]);
+
+        let sorted: Vec<i64> = order.iter().map(|crab| self.starting_positions[*crab] as i64).collect();
+        let crab_count = sorted.len();
+        let k = k.clamp(1, crab_count);
+
+        let mut prefix_sums = vec![0i64; crab_count + 1];
+        for (i, position) in sorted.iter().enumerate() {
+            prefix_sums[i + 1] = prefix_sums[i] + position;
+        }
+
+        // cost of sending sorted[first..=last] to their lower median
+        let group_cost = |first: usize, last: usize| -> u128 {
+            let median = (first + last) / 2;
+            let left = sorted[median] * (median - first + 1) as i64 - (prefix_sums[median + 1] - prefix_sums[first]);
+            let right = (prefix_sums[last + 1] - prefix_sums[median + 1]) - sorted[median] * (last - median) as i64;
+            return (left + right) as u128;
+        };
+
+        // best[groups][n]: cheapest split of the first n crabs into `groups` groups,
+        // split_at[groups][n]: where the last of those groups starts
+        let mut best = vec![vec![u128::MAX; crab_count + 1]; k + 1];
+        let mut split_at = vec![vec![0usize; crab_count + 1]; k + 1];
+        best[0][0] = 0;
+
+        for groups in 1..=k {
+            for n in groups..=crab_count {
+                for first in (groups - 1)..n {
+                    if best[groups - 1][first] == u128::MAX {
+                        continue;
+                    }
+
+                    let cost = best[groups - 1][first] + group_cost(first, n - 1);
+                    if cost < best[groups][n] {
+                        best[groups][n] = cost;
+                        split_at[groups][n] = first;
+                    }
+                }
+            }
+        }
+
+        let mut targets = vec![];
+        let mut n = crab_count;
+
+        for groups in (1..=k).rev() {
+            let first = split_at[groups][n];
+            let median = (first + n - 1) / 2;
+
+            targets.push(BlastTarget {
+                position: sorted[median] as i32,
+                crabs: order[first..n].to_vec(),
+                cost: group_cost(first, n - 1),
+            });
+
+            n = first;
+        }
+
+        targets.reverse();
 
-fn sum_fuel_cost_function(distance: i32) -> i32 {
-    // Gaussian Sum formula
-    return (distance.pow(2) + distance) / 2;
+        return MultiBlastLocation {
+            cost: best[k][crab_count],
+            targets
+        };
+    }
+
+    /// With linear fuel burn the lower median is the leftmost optimum.
+    fn calculate_blast_position_median(&self) -> BlastLocation {
+        let mut positions = self.starting_positions.clone();
+        let median_index = (positions.len() - 1) / 2;
+        let (_, median, _) = positions.select_nth_unstable(median_index);
+        let median = *median;
+
+        return BlastLocation::new(median, self.get_fuel_cost(median, &LinearCost));
+    }
+
+    /// With triangular fuel burn the optimum lies within half a step of the mean, so only the
+    /// positions around it need to be checked.
+    fn calculate_blast_position_near_mean(&self) -> BlastLocation {
+        let position_sum: i64 = self.starting_positions.iter().map(|x| *x as i64).sum();
+        let mean_floor = position_sum.div_euclid(self.starting_positions.len() as i64);
+
+        // the window around the mean may reach past the i32 range, the crabs never do
+        let first = (mean_floor - 1).max(self.min_pos as i64) as i32;
+        let last = (mean_floor + 2).min(self.max_pos as i64) as i32;
+
+        let mut best = BlastLocation::new(self.min_pos, u128::MAX);
+
+        for blast_pos in first..=last {
+            let fuel_cost = self.get_fuel_cost(blast_pos, &TriangularCost);
+
+            if fuel_cost < best.cost {
+                best = BlastLocation::new(blast_pos, fuel_cost);
+            }
+        }
+
+        return best;
+    }
 }
 
 fn main() {
@@ -75,9 +365,34 @@ fn main() {
                         .help("Input file to parse.")
                         .required(true)
                         .index(1))
+                    .arg(Arg::with_name("BRUTE_FORCE")
+                        .help("Try every position instead of using the median and mean shortcuts.")
+                        .long("brute-force"))
+                    .arg(Arg::with_name("COST_MODEL")
+                        .help("Only solve for this cost model: linear, triangular or quadratic.")
+                        .long("cost-model")
+                        .takes_value(true))
+                    .arg(Arg::with_name("CAP")
+                        .help("Maximum fuel a single crab burns under --cost-model.")
+                        .long("cap")
+                        .takes_value(true)
+                        .requires("COST_MODEL"))
+                    .arg(Arg::with_name("WEIGHTS")
+                        .help("Comma separated fuel multiplier per crab under --cost-model, in input order.")
+                        .long("weights")
+                        .takes_value(true)
+                        .requires("COST_MODEL"))
+                    .arg(Arg::with_name("COST_CURVE")
+                        .help("Print the cost of every position under --cost-model as CSV.")
+                        .long("cost-curve")
+                        .requires("COST_MODEL"))
+                    .arg(Arg::with_name("TARGETS")
+                        .help("Split the crabs between this many blast positions, with constant fuel burn.")
+                        .long("targets")
+                        .takes_value(true))
                     .get_matches();
 
-    let input = std::fs::read_to_string(&matches.value_of("INPUT").unwrap())
+    let input = std::fs::read_to_string(matches.value_of("INPUT").unwrap())
         .expect("Failed to open the readings file");
 
     let crabs: CrabArea = input
@@ -87,8 +402,69 @@ fn main() {
         .try_into()
         .unwrap();
 
-    let constant_cost_blast = crabs.calculate_blast_position(constant_fuel_cost_function);
-    let sum_cost_blast = crabs.calculate_blast_position(sum_fuel_cost_function);
+    if let Some(target_count) = matches.value_of("TARGETS") {
+        let blasts = crabs.calculate_blast_positions(target_count.parse().unwrap());
+
+        for target in blasts.targets.iter() {
+            println!("Blast position {}: {} crabs, cost {}", target.position, target.crabs.len(), target.cost);
+        }
+        println!("Total cost: {}", blasts.cost);
+        return;
+    }
+
+    if let Some(model_name) = matches.value_of("COST_MODEL") {
+        let mut cost_model: Box<dyn FuelCostModel> = model_name.try_into().unwrap();
+
+        if let Some(weights) = matches.value_of("WEIGHTS") {
+            let weights: Vec<u64> = weights.split(',').map(|x| x.parse().unwrap()).collect();
+            if weights.len() != crabs.starting_positions.len() {
+                panic!("Got {} weights for {} crabs", weights.len(), crabs.starting_positions.len());
+            }
+            cost_model = Box::new(WeightedCost { inner: cost_model, weights });
+        }
+
+        if let Some(cap) = matches.value_of("CAP") {
+            cost_model = Box::new(CappedCost { inner: cost_model, cap: cap.parse().unwrap() });
+        }
+
+        if matches.is_present("COST_CURVE") {
+            println!("position,cost");
+            for (position, cost) in crabs.get_cost_curve(cost_model.as_ref()) {
+                println!("{},{}", position, cost);
+            }
+            return;
+        }
+
+        let blast = if matches.is_present("BRUTE_FORCE") {
+            crabs.calculate_blast_position_brute_force(cost_model.as_ref())
+        } else {
+            crabs.calculate_blast_position(cost_model.as_ref())
+        };
+
+        let tied_positions = crabs.get_tied_positions(&blast, cost_model.as_ref());
+
+        println!("Blast position {}, cost {}", blast.position, blast.cost);
+        if tied_positions.len() > 1 || tied_positions[0].start() != tied_positions[0].end() {
+            let tied: Vec<String> = tied_positions
+                .iter()
+                .map(|run| match run.start() == run.end() {
+                    true => run.start().to_string(),
+                    false => format!("{}..={}", run.start(), run.end()),
+                })
+                .collect();
+            println!("Positions sharing the minimum cost: {}, the leftmost one was picked", tied.join(", "));
+        }
+        return;
+    }
+
+    let (constant_cost_blast, sum_cost_blast) = if matches.is_present("BRUTE_FORCE") {
+        (
+            crabs.calculate_blast_position_brute_force(&LinearCost),
+            crabs.calculate_blast_position_brute_force(&TriangularCost)
+        )
+    } else {
+        (crabs.calculate_blast_position_median(), crabs.calculate_blast_position_near_mean())
+    };
 
     println!("Constant Fuel Burn: blast position {}, cost {}", constant_cost_blast.position, constant_cost_blast.cost);
     println!("Summed Fuel Burn: blast position {}, cost {}", sum_cost_blast.position, sum_cost_blast.cost);
@@ -101,15 +477,190 @@ const TEST_INPUT: &str = "16,1,2,0,4,2,7,1,2,14";
 #[test]
 fn test_example_constant_fuel_burn() {
     let crab_area: CrabArea = TEST_INPUT.try_into().unwrap();
-    let blast = crab_area.calculate_blast_position(constant_fuel_cost_function);
+    let blast = crab_area.calculate_blast_position_brute_force(&LinearCost);
     assert_eq!(blast.position, 2);
     assert_eq!(blast.cost, 37);
+    assert_eq!(crab_area.calculate_blast_position_median(), blast);
 }
 
 #[test]
 fn test_example_sum_fuel_burn() {
     let crab_area: CrabArea = TEST_INPUT.try_into().unwrap();
-    let blast = crab_area.calculate_blast_position(sum_fuel_cost_function);
+    let blast = crab_area.calculate_blast_position_brute_force(&TriangularCost);
     assert_eq!(blast.position, 5);
     assert_eq!(blast.cost, 168);
+    assert_eq!(crab_area.calculate_blast_position_near_mean(), blast);
+}
+
+
+// a single crab, even and odd counts, duplicates, outliers on either side and tight clusters
+#[cfg(test)]
+const SCATTERED_INPUTS: [&str; 15] = [
+    "7",
+    "5,5",
+    "0,39",
+    "3,3,3",
+    "0,1,39",
+    "0,38,39",
+    "12,7,30,7",
+    "1,2,3,4,5",
+    "20,0,0,0,20",
+    "39,0,17,22,5,11",
+    "9,9,10,10,30,31",
+    "2,36,18,18,4,33,27",
+    "0,0,0,0,0,0,39",
+    "15,16,17,30,31,32,33",
+    "8,25,3,39,14,0,21,6",
+];
+
+#[test]
+fn test_fast_solvers_match_brute_force() {
+    for input in SCATTERED_INPUTS {
+        let crab_area: CrabArea = input.try_into().unwrap();
+
+        assert_eq!(crab_area.calculate_blast_position_median(), crab_area.calculate_blast_position_brute_force(&LinearCost), "{}", input);
+        assert_eq!(crab_area.calculate_blast_position_near_mean(), crab_area.calculate_blast_position_brute_force(&TriangularCost), "{}", input);
+    }
+}
+
+#[test]
+fn test_rightmost_position_is_considered() {
+    let crab_area: CrabArea = "0,10,10".try_into().unwrap();
+
+    assert_eq!(crab_area.calculate_blast_position_brute_force(&LinearCost), BlastLocation::new(10, 10));
+    assert_eq!(crab_area.calculate_blast_position_median(), BlastLocation::new(10, 10));
+}
+
+#[test]
+fn test_large_range() {
+    let crab_area: CrabArea = "0,3000000,5000000,9000000".try_into().unwrap();
+
+    assert_eq!(crab_area.calculate_blast_position_median(), BlastLocation::new(3000000, 11000000));
+    assert_eq!(crab_area.calculate_blast_position_near_mean().position, 4250000);
+}
+
+#[test]
+fn test_convex_search_matches_brute_force() {
+    let models: Vec<Box<dyn FuelCostModel>> = vec![Box::new(LinearCost), Box::new(TriangularCost), Box::new(QuadraticCost)];
+
+    for input in SCATTERED_INPUTS {
+        let crab_area: CrabArea = input.try_into().unwrap();
+
+        for model in models.iter() {
+            assert_eq!(crab_area.calculate_blast_position(model.as_ref()), crab_area.calculate_blast_position_brute_force(model.as_ref()), "{}", input);
+        }
+    }
+}
+
+#[test]
+fn test_composed_models() {
+    let crab_area: CrabArea = "0,1,10".try_into().unwrap();
+
+    let weighted = WeightedCost { inner: Box::new(LinearCost), weights: vec![1, 1, 5] };
+    assert!(weighted.is_convex());
+    assert_eq!(crab_area.calculate_blast_position(&weighted), BlastLocation::new(10, 19));
+
+    // capping makes the far crab cheap, so the blast moves to the other two
+    let capped = CappedCost { inner: Box::new(QuadraticCost), cap: 4 };
+    assert!(!capped.is_convex());
+    assert_eq!(crab_area.calculate_blast_position(&capped), BlastLocation::new(0, 5));
+}
+
+#[test]
+fn test_wide_range_costs() {
+    let crab_area: CrabArea = "-2000000000,2000000000".try_into().unwrap();
+
+    let blast = crab_area.calculate_blast_position(&QuadraticCost);
+    assert_eq!(blast, BlastLocation::new(0, 8_000_000_000_000_000_000));
+    assert_eq!(crab_area.calculate_blast_position_near_mean(), BlastLocation::new(0, 4_000_000_002_000_000_000));
+
+    // the search window around the mean reaches past either end of the i32 range
+    let crab_area: CrabArea = "2147483647,2147483647".try_into().unwrap();
+    assert_eq!(crab_area.calculate_blast_position_near_mean(), BlastLocation::new(2147483647, 0));
+
+    let crab_area: CrabArea = "-2147483648,-2147483648".try_into().unwrap();
+    assert_eq!(crab_area.calculate_blast_position_near_mean(), BlastLocation::new(-2147483648, 0));
+}
+
+#[test]
+fn test_multi_target_example() {
+    let crab_area: CrabArea = TEST_INPUT.try_into().unwrap();
+
+    let single = crab_area.calculate_blast_positions(1);
+    assert_eq!(single.cost, 37);
+    assert_eq!(single.targets.len(), 1);
+    assert_eq!(single.targets[0].position, 2);
+    assert_eq!(single.targets[0].crabs.len(), 10);
+
+    // 0,1,1,2,2,2,4,7 at 2 for 11 and 14,16 at 14 for 2
+    let double = crab_area.calculate_blast_positions(2);
+    assert_eq!(double.cost, 13);
+    assert_eq!(double.targets.iter().map(|x| x.position).collect::<Vec<i32>>(), vec![2, 14]);
+    assert_eq!(double.targets[1].crabs, vec![9, 0]);
+
+    let everyone_alone = crab_area.calculate_blast_positions(20);
+    assert_eq!(everyone_alone.cost, 0);
+}
+
+#[test]
+fn test_multi_target_matches_brute_force() {
+    for input in SCATTERED_INPUTS {
+        let crab_area: CrabArea = input.try_into().unwrap();
+
+        // every split into two groups of the sorted crabs, each solved on its own
+        let mut sorted = crab_area.starting_positions.clone();
+        sorted.sort();
+        let best_split = (1..sorted.len()).map(|split| {
+            let join = |group: &[i32]| group.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(",");
+            let left: CrabArea = join(&sorted[..split]).as_str().try_into().unwrap();
+            let right: CrabArea = join(&sorted[split..]).as_str().try_into().unwrap();
+            left.calculate_blast_position_brute_force(&LinearCost).cost + right.calculate_blast_position_brute_force(&LinearCost).cost
+        }).min().unwrap_or(0);
+
+        let blasts = crab_area.calculate_blast_positions(2);
+        assert_eq!(blasts.cost, best_split, "{}", input);
+        assert_eq!(blasts.cost, blasts.targets.iter().map(|x| x.cost).sum::<u128>());
+    }
+}
+
+#[test]
+fn test_cost_curve() {
+    let crab_area: CrabArea = TEST_INPUT.try_into().unwrap();
+    let curve = crab_area.get_cost_curve(&LinearCost);
+
+    assert_eq!(curve.len(), 17);
+    assert_eq!(curve[0], (0, 49));
+    assert_eq!(curve[1], (1, 41));
+    assert_eq!(curve[2], (2, 37));
+    assert_eq!(curve[3], (3, 39));
+    assert_eq!(curve[10], (10, 71));
+    assert_eq!(curve.last().unwrap().0, 16);
+}
+
+#[test]
+fn test_tied_positions() {
+    let crab_area: CrabArea = "0,2,8,10".try_into().unwrap();
+
+    let blast = crab_area.calculate_blast_position(&LinearCost);
+    assert_eq!(blast, BlastLocation::new(2, 16));
+    assert_eq!(crab_area.get_tied_positions(&blast, &LinearCost), vec![2..=8]);
+
+    let capped = CappedCost { inner: Box::new(LinearCost), cap: 1 };
+    let blast = crab_area.calculate_blast_position(&capped);
+    assert_eq!(blast, BlastLocation::new(0, 3));
+    assert_eq!(crab_area.get_tied_positions(&blast, &capped), vec![0..=0, 2..=2, 8..=8, 10..=10]);
+
+    let crab_area: CrabArea = "0,1,10".try_into().unwrap();
+    let blast = crab_area.calculate_blast_position(&capped);
+    assert_eq!(crab_area.get_tied_positions(&blast, &capped), vec![0..=1, 10..=10]);
+
+    let crab_area: CrabArea = "0,3,50000000,50000003".try_into().unwrap();
+    let blast = crab_area.calculate_blast_position(&LinearCost);
+    assert_eq!(blast, BlastLocation::new(3, 100000000));
+    assert_eq!(crab_area.get_tied_positions(&blast, &LinearCost), vec![3..=50000000]);
+
+    // a single minimum at the rightmost crab
+    let crab_area: CrabArea = "0,10,10".try_into().unwrap();
+    let blast = crab_area.calculate_blast_position(&LinearCost);
+    assert_eq!(crab_area.get_tied_positions(&blast, &LinearCost), vec![10..=10]);
 }
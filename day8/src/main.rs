@@ -1,89 +1,571 @@
-use std::{error::Error, collections::{HashMap, HashSet}, vec};
+#![allow(clippy::needless_return)]
+
+use std::{error::Error, collections::BTreeMap, fmt::Display};
 
 use clap::{App, Arg};
 use itertools::Itertools;
 
+/// Lit segments of the digits 0 to 9 on an intact display.
+const DIGIT_SEGMENTS: [(char, &str); 10] = [
+    ('0', "abcefg"), ('1', "cf"), ('2', "acdeg"), ('3', "acdfg"), ('4', "bcdf"),
+    ('5', "abdfg"), ('6', "abdefg"), ('7', "acf"), ('8', "abcdefg"), ('9', "abcdfg"),
+];
+
+const HEX_LETTER_SEGMENTS: [(char, &str); 6] = [
+    ('A', "abcdef"), ('b', "bdefg"), ('C', "abeg"), ('d', "cdefg"), ('E', "abdeg"), ('F', "abde"),
+];
+
+/// Segments a to f run clockwise around the frame from the top, g and h are the left and right
+/// halves of the middle bar, i, j and k the upper diagonals and centre bar from left to right,
+/// l, m and n the lower ones.
+const FOURTEEN_SEGMENT_GLYPHS: [(char, &str); 36] = [
+    ('0', "abcdefkl"), ('1', "bck"), ('2', "abdegh"), ('3', "abcdh"), ('4', "bcfgh"),
+    ('5', "adfgn"), ('6', "acdefgh"), ('7', "abc"), ('8', "abcdefgh"), ('9', "abcdfgh"),
+    ('A', "abcefgh"), ('B', "abcdhjm"), ('C', "adef"), ('D', "abcdjm"), ('E', "adefg"),
+    ('F', "aefg"), ('G', "acdefh"), ('H', "bcefgh"), ('I', "adjm"), ('J', "bcde"),
+    ('K', "efgkn"), ('L', "def"), ('M', "bcefik"), ('N', "bcefin"), ('O', "abcdef"),
+    ('P', "abefgh"), ('Q', "abcdefn"), ('R', "abefghn"), ('S', "acdfgh"), ('T', "ajm"),
+    ('U', "bcdef"), ('V', "efkl"), ('W', "bcefln"), ('X', "ikln"), ('Y', "ikm"),
+    ('Z', "adkl"),
+];
+
+/// Lit segments as a bit set, bit `i` standing for the `i`-th segment of the display alphabet.
+type SegmentMask = u32;
+
+/// Upper bound on the segments of a display, keeping the pattern lookup table at most 64k entries.
+const MAX_SEGMENTS: usize = 16;
+
+/// The symbols a display can show and the segments lit for each of them.
+#[derive(Clone, Debug)]
+struct GlyphTable {
+    /// Every segment of the display, sorted.
+    segments: String,
+    /// Symbol and its lit segments.
+    glyphs: Vec<(char, SegmentMask)>,
+}
+
+impl GlyphTable {
+    fn new(glyphs: &[(char, &str)]) -> Result<Self, Box<dyn Error>> {
+        let segments: String = glyphs.iter().flat_map(|(_, segments)| segments.chars()).unique().sorted().collect();
+
+        if let Some(segment) = segments.chars().find(|segment| !segment.is_ascii_graphic()) {
+            return Err(format!("Segment '{}' is not a printable ASCII character", segment).into());
+        }
+        if segments.len() > MAX_SEGMENTS {
+            return Err(format!("Displays can have at most {} segments, found {}", MAX_SEGMENTS, segments.len()).into());
+        }
+
+        let mut table = GlyphTable {
+            segments,
+            glyphs: vec![]
+        };
+
+        for (symbol, segments) in glyphs.iter() {
+            let mask = table.get_mask(segments).unwrap();
+
+            if table.glyphs.iter().any(|(_, other)| *other == mask) {
+                return Err(format!("Glyph '{}' shares its segments with another glyph", symbol).into());
+            }
+            table.glyphs.push((*symbol, mask));
+        }
+
+        return Ok(table);
+    }
+
+    fn seven_segment_decimal() -> Self {
+        return GlyphTable::new(&DIGIT_SEGMENTS).unwrap();
+    }
+
+    fn seven_segment_hex() -> Self {
+        let glyphs: Vec<(char, &str)> = DIGIT_SEGMENTS.iter().chain(HEX_LETTER_SEGMENTS.iter()).copied().collect();
+        return GlyphTable::new(&glyphs).unwrap();
+    }
+
+    fn fourteen_segment_alphanumeric() -> Self {
+        return GlyphTable::new(&FOURTEEN_SEGMENT_GLYPHS).unwrap();
+    }
+
+    fn get_segment_count(&self) -> usize {
+        return self.segments.len();
+    }
+
+    /// Mask with every segment of the display lit.
+    fn get_full_mask(&self) -> SegmentMask {
+        return (1 << self.get_segment_count()) - 1;
+    }
+
+    /// Translates segment (or wire) letters into a mask, `None` if a letter isn't part of the display.
+    fn get_mask(&self, pattern: &str) -> Option<SegmentMask> {
+        let mut mask = 0;
+
+        for c in pattern.chars() {
+            mask |= 1 << self.segments.find(c)?;
+        }
+
+        return Some(mask);
+    }
+
+    /// Base the symbols count in, if they are all digits of it.
+    fn get_radix(&self) -> Option<u32> {
+        let radix = self.glyphs.len() as u32;
+
+        if (2..=36).contains(&radix) && self.glyphs.iter().all(|(symbol, _)| symbol.is_digit(radix)) {
+            return Some(radix);
+        }
+
+        return None;
+    }
+
+    /// Symbols that can be told apart by their number of lit segments alone.
+    fn get_unique_length_symbols(&self) -> Vec<char> {
+        return self.glyphs
+            .iter()
+            .filter(|(_, mask)| self.glyphs.iter().filter(|(_, other)| other.count_ones() == mask.count_ones()).count() == 1)
+            .map(|(symbol, _)| *symbol)
+            .collect();
+    }
+}
+
+/// One glyph per line, the symbol followed by its lit segments, e.g. `7 acf`.
+impl TryFrom<&str> for GlyphTable {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut glyphs = vec![];
+
+        for line in value.lines().filter(|line| !line.trim().is_empty()) {
+            let (symbol, segments) = match line.trim().split_once(' ') {
+                Some(x) => x,
+                None => return Err(format!("Failed to parse glyph: \"{}\"", line).into()),
+            };
+
+            let mut symbol_chars = symbol.chars();
+            match (symbol_chars.next(), symbol_chars.next()) {
+                (Some(symbol), None) => glyphs.push((symbol, segments.trim())),
+                _ => return Err(format!("Glyph symbol has to be a single character: \"{}\"", symbol).into()),
+            }
+        }
+
+        return GlyphTable::new(&glyphs);
+    }
+}
+
+/// Assignment of each wire to the display segment it drives.
+type WireMapping = BTreeMap<char, char>;
+
+/// Number of wire mappings after which the search gives up on a line as ambiguous. Sparse lines
+/// on large displays allow far too many to enumerate.
+const MAPPING_LIMIT: usize = 6;
+
+#[derive(Debug)]
+enum SolverError {
+    /// A pattern uses a wire that doesn't exist.
+    UnknownWire(char),
+    /// The patterns contradict each other or the digit table.
+    NoMapping,
+    /// More than one wire mapping explains all patterns, holds at most `MAPPING_LIMIT` of them.
+    Ambiguous(Vec<WireMapping>),
+}
+
+impl Display for SolverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolverError::UnknownWire(wire) => write!(f, "Unknown wire '{}'", wire),
+            SolverError::NoMapping => write!(f, "No wire mapping explains the observed patterns"),
+            SolverError::Ambiguous(candidates) => {
+                if candidates.len() >= MAPPING_LIMIT {
+                    write!(f, "At least {} wire mappings explain the observed patterns:", candidates.len())?;
+                } else {
+                    write!(f, "{} wire mappings explain the observed patterns:", candidates.len())?;
+                }
+                for candidate in candidates.iter().take(5) {
+                    let formatted: String = candidate.values().collect();
+                    write!(f, " {}", formatted)?;
+                }
+                if candidates.len() > 5 {
+                    write!(f, " ...")?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+impl Error for SolverError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FaultKind {
+    StuckOn,
+    StuckOff,
+}
+
+/// A display segment that doesn't follow its wire.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct SegmentFault {
+    wire: char,
+    segment: char,
+    kind: FaultKind,
+}
+
+impl Display for SegmentFault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match self.kind {
+            FaultKind::StuckOn => "stuck on",
+            FaultKind::StuckOff => "stuck off",
+        };
+
+        return write!(f, "segment {} (wire {}) {}", self.segment, self.wire, state);
+    }
+}
+
+/// `mapping[wire]` is the index of the segment driven by the wire.
+type SegmentIndices = [u8; MAX_SEGMENTS];
+
+/// Wire mappings found by the search, at most `MAPPING_LIMIT` of them.
+struct MappingBuffer {
+    mappings: [SegmentIndices; MAPPING_LIMIT],
+    len: usize
+}
+
+impl MappingBuffer {
+    fn new() -> Self {
+        return MappingBuffer {
+            mappings: [[0; MAX_SEGMENTS]; MAPPING_LIMIT],
+            len: 0
+        };
+    }
+
+    fn is_full(&self) -> bool {
+        return self.len == MAPPING_LIMIT;
+    }
+
+    fn insert(&mut self, mapping: &SegmentIndices) {
+        if !self.is_full() && !self.get_mappings().contains(mapping) {
+            self.mappings[self.len] = *mapping;
+            self.len += 1;
+        }
+    }
+
+    fn get_mappings(&self) -> &[SegmentIndices] {
+        return &self.mappings[..self.len];
+    }
+}
+
 struct SevenSegmentSolver {
-    mapping: HashMap<String, u8>
+    glyphs: GlyphTable,
+    /// Mask bit of each ASCII wire letter, zero for letters that aren't wires.
+    wire_bits: [SegmentMask; 128],
+    /// Decoded symbol for every possible wire pattern.
+    lookup: Vec<Option<char>>,
+    /// Wire pattern each glyph shows up as with the current mapping, to clear the lookup table.
+    shown: Vec<usize>,
+    /// Scratch space for the observed patterns of a line.
+    observed: Vec<SegmentMask>,
+    /// Scratch space for the glyphs the search has assigned.
+    used_glyphs: Vec<bool>
 }
 
 impl SevenSegmentSolver {
+    /// A solver decoding nothing until it is given a line to `solve`. It keeps its buffers, so
+    /// solving line after line doesn't allocate.
+    fn new(glyphs: GlyphTable) -> Self {
+        let mut wire_bits = [0; 128];
+
+        for (i, wire) in glyphs.segments.bytes().enumerate() {
+            wire_bits[wire as usize] = 1 << i;
+        }
+
+        return SevenSegmentSolver {
+            wire_bits,
+            lookup: vec![None; 1 << glyphs.get_segment_count()],
+            shown: vec![0; glyphs.glyphs.len()],
+            observed: vec![],
+            used_glyphs: vec![false; glyphs.glyphs.len()],
+            glyphs
+        };
+    }
+
+    /// Finds the wire mapping by matching observed patterns to glyphs and narrowing down the
+    /// segments each wire could drive, backtracking when a wire runs out of candidates.
+    fn solve(&mut self, observed_values: &[&str]) -> Result<(), SolverError> {
+        self.clear_lookup();
+        self.set_observed_masks(observed_values)?;
+        self.observed.sort_unstable();
+        self.observed.dedup();
+
+        let mut solutions = MappingBuffer::new();
+        self.find_mappings(0, &mut solutions);
+
+        match solutions.len {
+            0 => Err(SolverError::NoMapping),
+            1 => {
+                self.fill_lookup(&solutions.mappings[0], 0, 0);
+                Ok(())
+            },
+            _ => Err(SolverError::Ambiguous(solutions.get_mappings().iter().map(|x| self.to_wire_mapping(x)).collect())),
+        }
+    }
+
+    /// Like `solve`, but allows for up to `max_faults` segments being stuck on or off. Each
+    /// observed pattern has to be a different glyph, and the mapping with the fewest faulty
+    /// segments wins.
+    fn diagnose(&mut self, observed_values: &[&str], max_faults: usize) -> Result<Vec<SegmentFault>, SolverError> {
+        self.clear_lookup();
+        self.set_observed_masks(observed_values)?;
+        let full = self.glyphs.get_full_mask();
+        let wire_count = self.glyphs.get_segment_count();
+
+        // a stuck wire reads the same in every pattern
+        let always_lit = self.observed.iter().fold(full, |mask, pattern| mask & pattern);
+        let never_lit = full & !self.observed.iter().fold(0, |mask, pattern| mask | pattern);
+        let suspects: Vec<usize> = (0..wire_count)
+            .filter(|wire| (always_lit | never_lit) & (1 << wire) != 0)
+            .collect();
+
+        for fault_count in 0..=max_faults.min(suspects.len()) {
+            let mut explanations: Vec<(SegmentMask, SegmentIndices)> = vec![];
+
+            for stuck in suspects.iter().combinations(fault_count) {
+                let stuck = stuck.iter().fold(0, |mask, wire| mask | (1 << **wire));
+
+                let mut solutions = MappingBuffer::new();
+                self.find_mappings(stuck, &mut solutions);
+                explanations.extend(solutions.get_mappings().iter().map(|mapping| (stuck, *mapping)));
+
+                if explanations.len() >= MAPPING_LIMIT {
+                    break;
+                }
+            }
+
+            match explanations.len() {
+                0 => continue,
+                1 => {
+                    let (stuck, wire_mapping) = explanations[0];
+                    let stuck_on = stuck & always_lit;
+                    let stuck_off = stuck & never_lit;
+
+                    let faults = (0..wire_count)
+                        .filter(|wire| stuck & (1 << wire) != 0)
+                        .map(|wire| SegmentFault {
+                            wire: self.glyphs.segments.as_bytes()[wire] as char,
+                            segment: self.glyphs.segments.as_bytes()[wire_mapping[wire] as usize] as char,
+                            kind: if stuck_on & (1 << wire) != 0 { FaultKind::StuckOn } else { FaultKind::StuckOff },
+                        })
+                        .collect();
+
+                    self.fill_lookup(&wire_mapping, stuck_on, stuck_off);
+                    return Ok(faults);
+                },
+                _ => return Err(SolverError::Ambiguous(explanations.iter().take(MAPPING_LIMIT).map(|(_, x)| self.to_wire_mapping(x)).collect())),
+            }
+        }
 
-    fn new(observed_values: Vec<&str>) -> Self {
-        // Solve and store mapping
+        return Err(SolverError::NoMapping);
+    }
 
-        let mut solver = SevenSegmentSolver {
-            mapping: HashMap::new()
+    fn set_observed_masks(&mut self, observed_values: &[&str]) -> Result<(), SolverError> {
+        self.observed.clear();
+
+        for pattern in observed_values.iter() {
+            match self.glyphs.get_mask(pattern) {
+                Some(mask) => self.observed.push(mask),
+                None => return Err(SolverError::UnknownWire(pattern.chars().find(|c| !self.glyphs.segments.contains(*c)).unwrap())),
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Wire mappings under which each observed pattern shows a different glyph. Wires in
+    /// `stuck` are ignored when comparing patterns to glyphs.
+    fn find_mappings(&mut self, stuck: SegmentMask, solutions: &mut MappingBuffer) {
+        let glyphs = &self.glyphs;
+
+        // patterns with few glyphs of the same length narrow things down fastest
+        self.observed.sort_unstable_by_key(|pattern| glyphs.glyphs.iter().filter(|(_, mask)| mask.count_ones() == pattern.count_ones()).count());
+
+        let candidates = [glyphs.get_full_mask(); MAX_SEGMENTS];
+        self.used_glyphs.fill(false);
+
+        SevenSegmentSolver::search(&self.observed, glyphs, stuck, candidates, &mut self.used_glyphs, solutions);
+    }
+
+    /// `candidates[wire]` holds the segments the wire could still drive.
+    fn search(observed: &[SegmentMask], glyphs: &GlyphTable, stuck: SegmentMask, candidates: [SegmentMask; MAX_SEGMENTS], used_glyphs: &mut [bool], solutions: &mut MappingBuffer) {
+        if solutions.is_full() {
+            return;
+        }
+
+        let wire_count = glyphs.get_segment_count();
+
+        let pattern = match observed.first() {
+            Some(x) => *x,
+            None => {
+                SevenSegmentSolver::enumerate_mappings(&candidates[..wire_count], 0, &mut [0; MAX_SEGMENTS], 0, solutions);
+                return;
+            },
         };
 
-        let two_on = observed_values.iter().find(|x| x.len() == 2).unwrap();
-        let four_on = observed_values.iter().find(|x| x.len() == 4).unwrap();
-        let seven_on = observed_values.iter().find(|x| x.len() == 3).unwrap();
-        let eight_on = observed_values.iter().find(|x| x.len() == 7).unwrap();
-        let six_on: Vec<HashSet<char>> = observed_values.iter().filter(|x| x.len() == 6).map(|x|HashSet::from_iter(x.chars())).collect();
-        let five_on: Vec<HashSet<char>> = observed_values.iter().filter(|x| x.len() == 5).map(|x|HashSet::from_iter(x.chars())).collect();
+        let full = glyphs.get_full_mask();
+        let lit = (pattern & !stuck).count_ones();
+
+        for (glyph, (_, segments)) in glyphs.glyphs.iter().enumerate() {
+            if used_glyphs[glyph] || segments.count_ones() < lit || segments.count_ones() > lit + stuck.count_ones() {
+                continue;
+            }
+
+            // wires of the pattern have to drive segments of the glyph, all others the remaining ones
+            let mut narrowed = candidates;
+            for (wire, possible) in narrowed.iter_mut().enumerate().take(wire_count) {
+                match (stuck & (1 << wire), pattern & (1 << wire)) {
+                    (0, 0) => *possible &= !segments & full,
+                    (0, _) => *possible &= segments,
+                    _ => (),
+                }
+            }
+
+            if narrowed[..wire_count].contains(&0) {
+                continue;
+            }
+
+            used_glyphs[glyph] = true;
+            SevenSegmentSolver::search(&observed[1..], glyphs, stuck, narrowed, used_glyphs, solutions);
+            used_glyphs[glyph] = false;
+        }
+    }
+
+    /// Collects the one-to-one wire mappings the candidate sets allow, `partial[..wire]` being
+    /// the segment indices of the wires assigned so far.
+    fn enumerate_mappings(candidates: &[SegmentMask], used_segments: SegmentMask, partial: &mut SegmentIndices, wire: usize, solutions: &mut MappingBuffer) {
+        if solutions.is_full() {
+            return;
+        }
 
-        let segment_cf: HashSet<char> = HashSet::from_iter(two_on.chars());
-        let segment_bcdf: HashSet<char> = HashSet::from_iter(four_on.chars());
-        let segment_acf: HashSet<char> = HashSet::from_iter(seven_on.chars());
-        let segment_abcdefg: HashSet<char> = HashSet::from_iter(eight_on.chars());
+        if wire == candidates.len() {
+            solutions.insert(partial);
+            return;
+        }
 
-        let segment_a = segment_acf.difference(&segment_cf).next().unwrap().clone();
+        let mut remaining = candidates[wire] & !used_segments;
 
-        let segment_bd: HashSet<char> = segment_bcdf.difference(&segment_cf).map(|x| x.clone()).collect();
+        while remaining != 0 {
+            let segment = remaining.trailing_zeros();
+            remaining &= remaining - 1;
 
-        let mut segment_cde: HashSet<char> = segment_abcdefg.clone();
+            partial[wire] = segment as u8;
+            SevenSegmentSolver::enumerate_mappings(candidates, used_segments | (1 << segment), partial, wire + 1, solutions);
+        }
+        partial[wire] = 0;
+    }
+
+    fn to_wire_mapping(&self, wire_mapping: &SegmentIndices) -> WireMapping {
+        return self.glyphs.segments
+            .chars()
+            .zip(wire_mapping.iter().map(|segment| self.glyphs.segments.as_bytes()[*segment as usize] as char))
+            .collect();
+    }
 
-        for c in segment_abcdefg.iter() {
-            if six_on.iter().fold(true, |acc, six_char| acc && six_char.contains(&c)) {
-                segment_cde.remove(&c);
+    fn clear_lookup(&mut self) {
+        for shown in self.shown.iter() {
+            self.lookup[*shown] = None;
+        }
+    }
+
+    /// Glyphs that look the same with the stuck wires can't be decoded.
+    fn fill_lookup(&mut self, wire_mapping: &SegmentIndices, stuck_on: SegmentMask, stuck_off: SegmentMask) {
+        let wire_count = self.glyphs.get_segment_count();
+
+        for (shown, (_, segments)) in self.shown.iter_mut().zip(self.glyphs.glyphs.iter()) {
+            let wires: SegmentMask = (0..wire_count)
+                .filter(|wire| segments & (1 << wire_mapping[*wire]) != 0)
+                .fold(0, |mask, wire| mask | (1 << wire));
+
+            *shown = ((wires | stuck_on) & !stuck_off) as usize;
+        }
+
+        for (glyph, (symbol, _)) in self.glyphs.glyphs.iter().enumerate() {
+            let shown = self.shown[glyph];
+
+            if self.shown.iter().filter(|other| **other == shown).count() == 1 {
+                self.lookup[shown] = Some(*symbol);
             }
         }
+    }
+
+    fn parse(&self, digit_value: &str) -> Option<char> {
+        let mut mask = 0;
 
-        let mut segment_bcef: HashSet<char> = segment_abcdefg.clone();
-        for c in segment_abcdefg.iter() {
-            if five_on.iter().fold(true, |acc, six_char| acc && six_char.contains(&c)) {
-                segment_bcef.remove(&c);
-            } 
+        for wire in digit_value.bytes() {
+            match self.wire_bits.get(wire as usize) {
+                Some(bit) if *bit != 0 => mask |= bit,
+                _ => return None,
+            }
         }
 
+        return self.lookup[mask as usize];
+    }
+}
 
-        let segment_b = segment_bd.difference(&segment_cde).next().unwrap().clone();
-        let segment_d = segment_bd.iter().filter(|c| **c != segment_b).next().unwrap().clone();
+/// Output digits of one display after decoding.
+#[derive(Debug, PartialEq, Eq)]
+struct DecodedLine {
+    /// Decoded symbols in display order, `None` where the pattern matches no glyph.
+    symbols: Vec<Option<char>>,
+}
 
-        let segment_f = segment_cf.difference(&segment_cde).next().unwrap().clone();
-        let segment_c = segment_cf.iter().filter(|c| **c != segment_f).next().unwrap().clone();
+impl DecodedLine {
+    fn new() -> Self {
+        return DecodedLine {
+            symbols: vec![]
+        };
+    }
 
-        let mut segment_eg = segment_abcdefg.clone();
-        segment_eg.remove(&segment_a);
-        segment_eg.remove(&segment_b);
-        segment_eg.remove(&segment_c);
-        segment_eg.remove(&segment_d);
-        segment_eg.remove(&segment_f);
+    /// Replaces the symbols with those of `to_decode`, reusing the buffer of the previous line.
+    fn decode(&mut self, solver: &SevenSegmentSolver, to_decode: &[&str]) {
+        self.symbols.clear();
+        self.symbols.extend(to_decode.iter().map(|digit| solver.parse(digit)));
+    }
 
-        let segment_g = segment_eg.difference(&segment_bcef).next().unwrap().clone();
-        let segment_e = segment_eg.iter().filter(|c| **c != segment_g).next().unwrap().clone();
+    /// Positions (from the left) of the digits that couldn't be decoded.
+    fn get_unknown_positions(&self) -> Vec<usize> {
+        return self.symbols
+            .iter()
+            .enumerate()
+            .filter(|(_, symbol)| symbol.is_none())
+            .map(|(pos, _)| pos)
+            .collect();
+    }
 
-        solver.mapping.insert(vec![segment_a, segment_b, segment_c, segment_e, segment_f, segment_g].iter().sorted().collect(), 0);
-        solver.mapping.insert(vec![segment_c, segment_f].iter().sorted().collect(), 1);
-        solver.mapping.insert(vec![segment_a, segment_c, segment_d, segment_e, segment_g].iter().sorted().collect(), 2);
-        solver.mapping.insert(vec![segment_a, segment_c, segment_d, segment_f, segment_g].iter().sorted().collect(), 3);
-        solver.mapping.insert(vec![segment_b, segment_c, segment_d, segment_f].iter().sorted().collect(), 4);
-        solver.mapping.insert(vec![segment_a, segment_b, segment_d, segment_f, segment_g].iter().sorted().collect(), 5);
-        solver.mapping.insert(vec![segment_a, segment_b, segment_d, segment_e, segment_f, segment_g].iter().sorted().collect(), 6);
-        solver.mapping.insert(vec![segment_a, segment_c, segment_f].iter().sorted().collect(), 7);
-        solver.mapping.insert(vec![segment_a, segment_b, segment_c, segment_d, segment_e, segment_f, segment_g].iter().sorted().collect(), 8);
-        solver.mapping.insert(vec![segment_a, segment_b, segment_c, segment_d, segment_f, segment_g].iter().sorted().collect(), 9);
-        
+    fn get_text(&self, wildcard: char) -> String {
+        return self.symbols.iter().map(|symbol| symbol.unwrap_or(wildcard)).collect();
+    }
 
-        return solver;
+    /// Numeric value of the digits, each unknown digit replaced by `fill`.
+    fn get_value(&self, radix: u32, fill: u32) -> u64 {
+        return self.symbols
+            .iter()
+            .map(|symbol| symbol.map_or(fill, |x| x.to_digit(radix).unwrap()))
+            .fold(0, |value, digit| value * radix as u64 + digit as u64);
     }
+}
 
-    fn parse(&self, digit_value: &str) -> Option<u8> {
-        let sorted: String = digit_value.chars().sorted().collect();
-        match self.mapping.get(&sorted) {
-            Some(x) => Some((*x).clone()),
-            None => None,
+/// What to do with output digits that match no glyph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum UnknownDigitPolicy {
+    /// Leave the whole line out of the results.
+    Reject,
+    /// Keep the line, the digit can be any symbol.
+    Wildcard,
+}
+
+impl TryFrom<&str> for UnknownDigitPolicy {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "error" => Ok(UnknownDigitPolicy::Reject),
+            "wildcard" => Ok(UnknownDigitPolicy::Wildcard),
+            _ => Err(format!("Unknown policy for undecodable digits: \"{}\"", value).into()),
         }
     }
 }
@@ -94,79 +576,387 @@ fn main() {
                         .help("Input file to parse.")
                         .required(true)
                         .index(1))
+                    .arg(Arg::with_name("GLYPHS")
+                        .help("Glyph table of the displays: decimal, hex, alphanumeric (14 segments) or a file with one \"<symbol> <segments>\" per line.")
+                        .long("glyphs")
+                        .takes_value(true)
+                        .default_value("decimal"))
+                    .arg(Arg::with_name("UNKNOWN_DIGITS")
+                        .help("Handling of output digits matching no glyph: \"error\" drops the line, \"wildcard\" keeps it and reports the range of possible sums.")
+                        .long("unknown-digits")
+                        .takes_value(true)
+                        .possible_values(&["error", "wildcard"])
+                        .default_value("error"))
+                    .arg(Arg::with_name("DIAGNOSE")
+                        .help("Allow for segments stuck on or off and report the fewest faults explaining each display.")
+                        .long("diagnose"))
+                    .arg(Arg::with_name("MAX_FAULTS")
+                        .help("Most faulty segments to consider per display when diagnosing, 2 by default.")
+                        .long("max-faults")
+                        .takes_value(true)
+                        .requires("DIAGNOSE"))
                     .get_matches();
 
-    let input = std::fs::read_to_string(&matches.value_of("INPUT").unwrap())
+    let input = std::fs::read_to_string(matches.value_of("INPUT").unwrap())
         .expect("Failed to open the readings file");
 
-    let unpacked_input_lines: Vec<(Vec<&str>, Vec<&str>)> = input
-        .split("\n")
-        .filter(|line| !line.is_empty())
-        .map(|line| unpack_input_line(line))
-        .collect(); 
-    
-    let mut digit_occurrances = [0u32;10];
-    let mut decoded_values: Vec<i32> = vec![];
+    let glyphs = match matches.value_of("GLYPHS").unwrap() {
+        "decimal" => GlyphTable::seven_segment_decimal(),
+        "hex" => GlyphTable::seven_segment_hex(),
+        "alphanumeric" => GlyphTable::fourteen_segment_alphanumeric(),
+        path => std::fs::read_to_string(path)
+            .expect("Failed to open the glyph table")
+            .as_str()
+            .try_into()
+            .unwrap(),
+    };
+
+    let policy: UnknownDigitPolicy = matches.value_of("UNKNOWN_DIGITS").unwrap().try_into().unwrap();
+    let radix = glyphs.get_radix();
+    let max_faults: Option<usize> = match matches.is_present("DIAGNOSE") {
+        true => Some(matches.value_of("MAX_FAULTS").unwrap_or("2").parse().expect("Failed to parse the fault limit")),
+        false => None,
+    };
+
+    let unique_length_symbols = glyphs.get_unique_length_symbols();
+    let mut unique_length_occurrences = 0;
+    let mut lowest_sum = 0;
+    let mut highest_sum = 0;
+    let mut failed_lines: Vec<(usize, String)> = vec![];
+
+    let mut solver = SevenSegmentSolver::new(glyphs.clone());
+    let mut observed_values: Vec<&str> = vec![];
+    let mut to_decode: Vec<&str> = vec![];
+    let mut decoded = DecodedLine::new();
+
+    for (line_number, line) in input.split('\n').filter(|line| !line.is_empty()).enumerate() {
+        unpack_input_line(line, &mut observed_values, &mut to_decode);
+
+        let solved = match max_faults {
+            Some(max_faults) => solver.diagnose(&observed_values, max_faults),
+            None => solver.solve(&observed_values).map(|_| vec![]),
+        };
 
-    for (observed_values, to_decode) in unpacked_input_lines {
-        let solver = SevenSegmentSolver::new(observed_values);
+        match solved {
+            Ok(faults) => {
+                if !faults.is_empty() {
+                    println!("Line {}: {}", line_number + 1, faults.iter().map(|x| x.to_string()).join(", "));
+                }
+            },
+            Err(e) => {
+                failed_lines.push((line_number + 1, e.to_string()));
+                continue;
+            },
+        };
 
-        let mut value = 0;
+        decoded.decode(&solver, &to_decode);
+        let unknown_positions = decoded.get_unknown_positions();
 
-        for (pos, digit) in to_decode.iter().rev().enumerate() {
-            match solver.parse(digit) {
-                Some(x) =>  {
-                    digit_occurrances[x as usize] += 1;
-                    value += 10i32.pow(pos.try_into().unwrap()) * x as i32;
-                },
-                None => continue,
+        if !unknown_positions.is_empty() {
+            let unknown_digits: Vec<&str> = unknown_positions.iter().map(|pos| to_decode[*pos]).collect();
+            failed_lines.push((line_number + 1, format!("Undecodable output digits: {}", unknown_digits.join(" "))));
+
+            if policy == UnknownDigitPolicy::Reject {
+                continue;
             }
         }
 
-        decoded_values.push(value);
+        unique_length_occurrences += decoded.symbols
+            .iter()
+            .flatten()
+            .filter(|x| unique_length_symbols.contains(x))
+            .count();
+
+        match radix {
+            Some(radix) => {
+                lowest_sum += decoded.get_value(radix, 0);
+                highest_sum += decoded.get_value(radix, radix - 1);
+            },
+            None => println!("Line {}: {}", line_number + 1, decoded.get_text('?')),
+        }
     }
 
-    let sum: i32 = decoded_values.iter().sum();
+    println!("Amount of digits with unique output values: {}", unique_length_occurrences);
 
-    println!("Amount of digits with unique output values: {}", digit_occurrances[1] + digit_occurrances[4] + digit_occurrances[7] + digit_occurrances[8]);
-    println!("Sum of decoded values: {}", sum);
+    if radix.is_some() {
+        if lowest_sum == highest_sum {
+            println!("Sum of decoded values: {}", lowest_sum);
+        } else {
+            println!("Sum of decoded values: between {} and {}", lowest_sum, highest_sum);
+        }
+    }
+
+    if !failed_lines.is_empty() {
+        println!("Lines that failed to decode: {}", failed_lines.len());
+        for (line_number, reason) in failed_lines.iter() {
+            eprintln!("Line {}: {}", line_number, reason);
+        }
+    }
 }
 
-fn unpack_input_line(line: &str) -> (Vec<&str>, Vec<&str>){
-    let (observed, digits) = line.split_once(" | ").unwrap();
-    return (observed.split(" ").collect(), digits.split(" ").collect());
+/// Splits `line` into the observed patterns and the digits to decode, refilling the buffers of the previous line.
+fn unpack_input_line<'a>(line: &'a str, observed: &mut Vec<&'a str>, to_decode: &mut Vec<&'a str>) {
+    let (observed_patterns, digits) = line.split_once(" | ").unwrap();
+
+    observed.clear();
+    observed.extend(observed_patterns.split(' '));
+    to_decode.clear();
+    to_decode.extend(digits.split(' '));
 }
 
 
 #[cfg(test)]
 const TEST_INPUT: &str = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf";
 
+#[cfg(test)]
+fn solve(glyphs: GlyphTable, observed: &[&str]) -> Result<SevenSegmentSolver, SolverError> {
+    let mut solver = SevenSegmentSolver::new(glyphs);
+    solver.solve(observed)?;
+    return Ok(solver);
+}
+
 #[cfg(test)]
 const TRIVIAL_INPUT: &str = "abcefg cf acdeg acdfg bcdf abdfg abdefg acf abcdefg abcdfg | cf cf cf cf";
 
 #[test]
 fn test_cheap_digit_identification() {
-    let (observed, to_parse) = unpack_input_line(TEST_INPUT);
+    let (mut observed, mut to_decode) = (vec![], vec![]);
+    unpack_input_line(TEST_INPUT, &mut observed, &mut to_decode);
 
-    let solver = SevenSegmentSolver::new(observed);
+    let solver = solve(GlyphTable::seven_segment_decimal(), &observed).unwrap();
 
-    assert_eq!(solver.parse("ab").unwrap(), 1);
-    assert_eq!(solver.parse("ba").unwrap(), 1);
+    assert_eq!(solver.parse("ab").unwrap(), '1');
+    assert_eq!(solver.parse("ba").unwrap(), '1');
     
-    assert_eq!(solver.parse("gcdfa").unwrap(), 2);
-    assert_eq!(solver.parse("fbcad").unwrap(), 3);
-    assert_eq!(solver.parse("eafb").unwrap(), 4);
-    assert_eq!(solver.parse("cdfbe").unwrap(), 5);
-    assert_eq!(solver.parse("cdfgeb").unwrap(), 6);
-    assert_eq!(solver.parse("dab").unwrap(), 7);
-    assert_eq!(solver.parse("acedgfb").unwrap(), 8);
-    assert_eq!(solver.parse("cefabd").unwrap(), 9);
-    assert_eq!(solver.parse("cagedb").unwrap(), 0);
+    assert_eq!(solver.parse("gcdfa").unwrap(), '2');
+    assert_eq!(solver.parse("fbcad").unwrap(), '3');
+    assert_eq!(solver.parse("eafb").unwrap(), '4');
+    assert_eq!(solver.parse("cdfbe").unwrap(), '5');
+    assert_eq!(solver.parse("cdfgeb").unwrap(), '6');
+    assert_eq!(solver.parse("dab").unwrap(), '7');
+    assert_eq!(solver.parse("acedgfb").unwrap(), '8');
+    assert_eq!(solver.parse("cefabd").unwrap(), '9');
+    assert_eq!(solver.parse("cagedb").unwrap(), '0');
 }
 
 #[test]
 fn test_on_identity() {
-    let (observed, to_parse) = unpack_input_line(TRIVIAL_INPUT);
+    let (mut observed, mut to_parse) = (vec![], vec![]);
+    unpack_input_line(TRIVIAL_INPUT, &mut observed, &mut to_parse);
+
+    let solver = solve(GlyphTable::seven_segment_decimal(), &observed).unwrap();
+    for digit in to_parse {
+        assert_eq!(solver.parse(digit), Some('1'));
+    }
+}
+
+#[test]
+fn test_partial_observations() {
+    // without 1, 4 and 8 the other patterns still pin down every wire
+    let solver = solve(GlyphTable::seven_segment_decimal(), &["dab", "cefabd", "cdfgeb", "cagedb", "cdfbe", "gcdfa", "fbcad"]).unwrap();
+
+    assert_eq!(solver.parse("ab"), Some('1'));
+    assert_eq!(solver.parse("eafb"), Some('4'));
+    assert_eq!(solver.parse("dab"), Some('7'));
+}
+
+#[test]
+fn test_ambiguous_observations() {
+    match solve(GlyphTable::seven_segment_decimal(), &["ab", "dab", "eafb", "acedgfb"]) {
+        // c/f, b/d and e/g on the real display can't be told apart from these, the search
+        // stops before finding all 8 mappings
+        Err(SolverError::Ambiguous(candidates)) => assert_eq!(candidates.len(), MAPPING_LIMIT),
+        _ => panic!("expected an ambiguous mapping"),
+    }
+
+    match solve(GlyphTable::seven_segment_decimal(), &["ab", "dab", "eafb", "acedgfb", "cdfbe"]) {
+        // every observed digit lights either both or neither of b and d, so wires e and f can swap
+        Err(SolverError::Ambiguous(candidates)) => assert_eq!(candidates.len(), 2),
+        _ => panic!("expected an ambiguous mapping"),
+    }
+}
+
+#[test]
+fn test_contradicting_observations() {
+    assert!(matches!(solve(GlyphTable::seven_segment_decimal(), &["ab", "cd"]), Err(SolverError::NoMapping)));
+    assert!(matches!(solve(GlyphTable::seven_segment_decimal(), &["ax"]), Err(SolverError::UnknownWire('x'))));
+}
+
+#[test]
+fn test_glyph_tables() {
+    let hex = GlyphTable::seven_segment_hex();
+    assert_eq!(hex.glyphs.len(), 16);
+    assert_eq!(hex.get_radix(), Some(16));
+    assert_eq!(GlyphTable::seven_segment_decimal().get_radix(), Some(10));
+    assert_eq!(GlyphTable::seven_segment_decimal().get_unique_length_symbols(), vec!['1', '4', '7', '8']);
+
+    let alphanumeric = GlyphTable::fourteen_segment_alphanumeric();
+    assert_eq!(alphanumeric.segments, "abcdefghijklmn");
+    assert_eq!(alphanumeric.get_radix(), Some(36));
+
+    let custom: GlyphTable = "x ab\ny bc\nz abc".try_into().unwrap();
+    assert_eq!(custom.segments, "abc");
+    assert_eq!(custom.get_radix(), None);
+
+    assert!(GlyphTable::try_from("x ab\ny ba").is_err());
+    assert!(GlyphTable::try_from("xy ab").is_err());
+}
+
+#[cfg(test)]
+fn get_pattern(glyphs: &GlyphTable, mask: SegmentMask) -> String {
+    // letters of the segments set in mask, in alphabet order
+    glyphs.segments.chars().enumerate().filter(|(i, _)| mask & (1 << i) != 0).map(|(_, c)| c).collect()
+}
+
+#[cfg(test)]
+fn scramble(segments: &str, wiring: &str) -> String {
+    // wiring[i] is the wire driving the i-th segment of the alphabet
+    segments.chars().map(|segment| wiring.chars().nth((segment as u8 - b'a') as usize).unwrap()).collect()
+}
+
+#[test]
+fn test_hex_decoding() {
+    let hex = GlyphTable::seven_segment_hex();
+    let wiring = "gfedcba";
+    let observed: Vec<String> = hex.glyphs.iter().map(|(_, mask)| scramble(&get_pattern(&hex, *mask), wiring)).collect();
+
+    let solver = solve(hex.clone(), &observed.iter().map(|x| x.as_str()).collect::<Vec<&str>>()).unwrap();
+
+    for (symbol, mask) in hex.glyphs.iter() {
+        assert_eq!(solver.parse(&scramble(&get_pattern(&hex, *mask), wiring)), Some(*symbol));
+    }
+}
+
+#[test]
+fn test_fourteen_segment_decoding() {
+    let alphanumeric = GlyphTable::fourteen_segment_alphanumeric();
+    let wiring = "nmlkjihgfedcba";
+    let observed: Vec<String> = alphanumeric.glyphs.iter().map(|(_, mask)| scramble(&get_pattern(&alphanumeric, *mask), wiring)).collect();
+
+    let solver = solve(alphanumeric.clone(), &observed.iter().map(|x| x.as_str()).collect::<Vec<&str>>()).unwrap();
+
+    let text: String = ["bcefgh", "adefg", "def", "def", "abcdef"]
+        .iter()
+        .map(|segments| solver.parse(&scramble(segments, wiring)).unwrap())
+        .collect();
+    assert_eq!(text, "HELLO");
+}
+
+#[test]
+fn test_pattern_masks() {
+    let decimal = GlyphTable::seven_segment_decimal();
+    assert_eq!(decimal.get_mask("cf"), Some(0b0100100));
+    assert_eq!(decimal.get_mask("fc"), Some(0b0100100));
+    assert_eq!(decimal.get_mask("cx"), None);
+    assert_eq!(get_pattern(&decimal, 0b1001001), "adg");
+    assert_eq!(decimal.get_full_mask(), 0b1111111);
+
+    let (mut observed, mut to_decode) = (vec![], vec![]);
+    unpack_input_line(TRIVIAL_INPUT, &mut observed, &mut to_decode);
+    let solver = solve(GlyphTable::seven_segment_decimal(), &observed).unwrap();
+    assert_eq!(solver.parse("fc"), Some('1'));
+    assert_eq!(solver.parse("cx"), None);
+    assert_eq!(solver.parse("ab"), None);
+}
+
+#[test]
+fn test_unknown_digits() {
+    let (mut observed, mut to_decode) = (vec![], vec![]);
+    unpack_input_line(TEST_INPUT, &mut observed, &mut to_decode);
+    let solver = solve(GlyphTable::seven_segment_decimal(), &observed).unwrap();
+
+    // "ag" isn't a glyph, the following digits must keep their place value
+    let mut decoded = DecodedLine::new();
+    decoded.decode(&solver, &["ab", "ag", "dab", "eafb"]);
+
+    assert_eq!(decoded.get_unknown_positions(), vec![1]);
+    assert_eq!(decoded.get_text('?'), "1?74");
+    assert_eq!(decoded.get_value(10, 0), 1074);
+    assert_eq!(decoded.get_value(10, 9), 1974);
+
+    decoded.decode(&solver, &["cdfeb", "fcadb", "cdfeb", "cdbaf"]);
+    assert!(decoded.get_unknown_positions().is_empty());
+    assert_eq!(decoded.get_value(10, 0), 5353);
+}
+
+#[cfg(test)]
+fn strip_wire(patterns: &str, wire: char) -> Vec<String> {
+    return patterns.split(' ').map(|x| x.replace(wire, "")).collect();
+}
 
-    let solver = SevenSegmentSolver::new(observed);
-}
\ No newline at end of file
+#[test]
+fn test_stuck_off_diagnosis() {
+    let (mut observed, mut to_decode) = (vec![], vec![]);
+    unpack_input_line(TEST_INPUT, &mut observed, &mut to_decode);
+    let faulty = strip_wire(&observed.join(" "), 'g');
+    let faulty: Vec<&str> = faulty.iter().map(|x| x.as_str()).collect();
+
+    assert!(matches!(solve(GlyphTable::seven_segment_decimal(), &faulty), Err(SolverError::NoMapping)));
+
+    let mut solver = SevenSegmentSolver::new(GlyphTable::seven_segment_decimal());
+    let faults = solver.diagnose(&faulty, 2).unwrap();
+
+    // wire g drives the lower left segment, without it 5 and 6 look alike
+    assert_eq!(faults, vec![SegmentFault { wire: 'g', segment: 'e', kind: FaultKind::StuckOff }]);
+    assert_eq!(solver.parse("cdfeb"), None);
+    assert_eq!(solver.parse("cadeb"), Some('0'));
+    assert_eq!(solver.parse("ab"), Some('1'));
+}
+
+#[test]
+fn test_stuck_on_diagnosis() {
+    let (mut observed, mut to_decode) = (vec![], vec![]);
+    unpack_input_line(TRIVIAL_INPUT, &mut observed, &mut to_decode);
+    // the top segment lights up in every digit, so 7 and 1 look alike
+    let faulty: Vec<String> = observed.iter().map(|x| if x.contains('a') { x.to_string() } else { format!("a{}", x) }).collect();
+    let faulty: Vec<&str> = faulty.iter().map(|x| x.as_str()).collect();
+
+    let mut solver = SevenSegmentSolver::new(GlyphTable::seven_segment_decimal());
+    let faults = solver.diagnose(&faulty, 1).unwrap();
+
+    assert_eq!(faults, vec![SegmentFault { wire: 'a', segment: 'a', kind: FaultKind::StuckOn }]);
+    assert_eq!(solver.parse("acf"), None);
+    assert_eq!(solver.parse("abcdefg"), Some('8'));
+
+    // an intact display needs no faults
+    let faults = solver.diagnose(&observed, 1).unwrap();
+    assert!(faults.is_empty());
+}
+
+#[test]
+fn test_underdetermined_fourteen_segment_line() {
+    let (mut observed, mut to_decode) = (vec![], vec![]);
+    unpack_input_line("abc bck | bck", &mut observed, &mut to_decode);
+
+    match solve(GlyphTable::fourteen_segment_alphanumeric(), &observed) {
+        Err(SolverError::Ambiguous(candidates)) => assert_eq!(candidates.len(), MAPPING_LIMIT),
+        _ => panic!("expected an ambiguous mapping"),
+    }
+}
+
+#[test]
+fn test_solver_reuse() {
+    let mut solver = SevenSegmentSolver::new(GlyphTable::seven_segment_decimal());
+
+    let (mut observed, mut to_decode) = (vec![], vec![]);
+    let mut decoded = DecodedLine::new();
+
+    unpack_input_line(TEST_INPUT, &mut observed, &mut to_decode);
+    solver.solve(&observed).unwrap();
+    decoded.decode(&solver, &to_decode);
+    assert_eq!(solver.parse("ab"), Some('1'));
+    assert_eq!(decoded.get_value(10, 0), 5353);
+
+    // the buffers only hold the second line afterwards
+    unpack_input_line("abcefg cf acdeg acdfg bcdf abdfg abdefg acf abcdefg abcdfg | cf acf", &mut observed, &mut to_decode);
+    assert_eq!((observed.len(), to_decode.len()), (10, 2));
+    solver.solve(&observed).unwrap();
+    decoded.decode(&solver, &to_decode);
+    assert_eq!(solver.parse("ab"), None);
+    assert_eq!(solver.parse("cf"), Some('1'));
+    assert_eq!(decoded.get_value(10, 0), 17);
+
+    // a failed line leaves nothing decodable behind
+    assert!(solver.solve(&["ab", "cd"]).is_err());
+    assert_eq!(solver.parse("cf"), None);
+}
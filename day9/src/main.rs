@@ -1,4 +1,6 @@
-use std::{error::Error, collections::HashSet};
+#![allow(clippy::needless_return)]
+
+use std::{error::Error, collections::VecDeque};
 
 use clap::{App, Arg};
 
@@ -6,18 +8,59 @@ struct Heightmap {
     // indexed y, x
     data: Vec<Vec<Point3D>>,
     width: usize,
-    height: usize
+    height: usize,
+    rules: TerrainRules
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Connectivity {
+    /// Only orthogonal neighbours touch.
+    Four,
+    /// Diagonal neighbours touch as well.
+    Eight,
+}
+
+impl TryFrom<&str> for Connectivity {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "4" => Ok(Connectivity::Four),
+            "8" => Ok(Connectivity::Eight),
+            _ => Err(format!("Connectivity has to be 4 or 8, got \"{}\"", value).into()),
+        }
+    }
+}
+
+/// How basins are delimited on a heightmap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct TerrainRules {
+    /// Cells at least this high separate basins.
+    wall_height: u32,
+    connectivity: Connectivity,
+    /// Whether a flat region lower than everything around it counts as a low point.
+    plateau_low_points: bool
+}
+
+impl TerrainRules {
+    fn standard() -> Self {
+        return TerrainRules {
+            wall_height: 9,
+            connectivity: Connectivity::Four,
+            plateau_low_points: false
+        };
+    }
 }
 
 #[derive(PartialEq, Eq, Hash, Debug)]
 struct Point3D {
     x: usize,
     y: usize,
-    z: u8
+    z: u32
 }
 
 impl Point3D {
-    fn new(x: usize, y: usize, z: u8) -> Self {
+    fn new(x: usize, y: usize, z: u32) -> Self {
         return Self {
             x, y, z
         }
@@ -25,96 +68,408 @@ impl Point3D {
 }
 
 impl Heightmap {
+    fn with_rules(self, rules: TerrainRules) -> Self {
+        return Heightmap {
+            rules,
+            ..self
+        };
+    }
+
+    fn is_wall(&self, point: &Point3D) -> bool {
+        return point.z >= self.rules.wall_height;
+    }
+
     fn get_neighbours_of_point(&self, point: &Point3D) -> Vec<&Point3D>{
         let mut neighbours = vec![];
 
-        if point.x != 0 {
-            neighbours.push(&self.data[point.y][point.x-1]);
-        }
+        for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1)] {
+            if self.rules.connectivity == Connectivity::Four && dx != 0 && dy != 0 {
+                continue;
+            }
 
-        if point.x != self.width - 1 {
-            neighbours.push(&self.data[point.y][point.x + 1]);
-        }
+            let x = point.x as isize + dx;
+            let y = point.y as isize + dy;
 
-        if point.y != 0 {
-            neighbours.push(&self.data[point.y - 1][point.x]);
+            if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+                neighbours.push(&self.data[y as usize][x as usize]);
+            }
         }
 
-        if point.y != self.height - 1 {
-            neighbours.push(&self.data[point.y + 1][point.x]);
+        return neighbours;
+    }
+
+    /// Splits the map into connected regions of equal height. Returns the region of every cell
+    /// in row-major order along with the cells of each region.
+    fn get_flat_regions(&self) -> (Vec<usize>, Vec<Vec<&Point3D>>) {
+        let mut region_of = vec![usize::MAX; self.width * self.height];
+        let mut regions = vec![];
+
+        for start in self.data.iter().flatten() {
+            if region_of[start.y * self.width + start.x] != usize::MAX {
+                continue;
+            }
+
+            region_of[start.y * self.width + start.x] = regions.len();
+            let mut region = vec![start];
+            let mut next = 0;
+
+            while next < region.len() {
+                for neighbour in self.get_neighbours_of_point(region[next]) {
+                    let index = neighbour.y * self.width + neighbour.x;
+
+                    if neighbour.z == start.z && region_of[index] == usize::MAX {
+                        region_of[index] = regions.len();
+                        region.push(neighbour);
+                    }
+                }
+                next += 1;
+            }
+
+            regions.push(region);
         }
 
-        return neighbours;
+        return (region_of, regions);
+    }
+
+    /// Neighbours of a region lower than the region itself.
+    fn get_lower_neighbours<'a>(&'a self, region: &[&'a Point3D]) -> Vec<&'a Point3D> {
+        return region
+            .iter()
+            .flat_map(|point| self.get_neighbours_of_point(point))
+            .filter(|neighbour| neighbour.z < region[0].z)
+            .collect();
+    }
+
+    /// Regions of equal height below the wall height whose neighbours are all higher. Without
+    /// plateau low points only single cells qualify.
+    fn get_low_regions(&self) -> Vec<Vec<&Point3D>> {
+        let (_, regions) = self.get_flat_regions();
+
+        return regions
+            .into_iter()
+            .filter(|region| !self.is_wall(region[0]) && (self.rules.plateau_low_points || region.len() == 1))
+            .filter(|region| self.get_lower_neighbours(region).is_empty())
+            .collect();
     }
 
     fn get_low_points(&self) -> Vec<&Point3D> {
-        let mut low_points: Vec<&Point3D> = vec![];
+        return self.get_low_regions().into_iter().flatten().collect();
+    }
 
-        for grid_point in self.data.iter().flatten() {
-            let is_lowpoint = self.get_neighbours_of_point(grid_point)
-                .iter()
-                .fold(true, |acc, neighbour| acc && neighbour.z > grid_point.z);
+    /// Labels every basin in a single breadth-first pass over the map, including basins that
+    /// don't contain a low point.
+    fn get_basin_map(&self) -> BasinMap {
+        let mut basins = BasinMap {
+            width: self.width,
+            labels: vec![None; self.width * self.height],
+            sizes: vec![]
+        };
+        let mut to_visit: VecDeque<&Point3D> = VecDeque::new();
+
+        for start in self.data.iter().flatten() {
+            if self.is_wall(start) || basins.get_label(start.x, start.y).is_some() {
+                continue;
+            }
+
+            let label = basins.sizes.len();
+            basins.sizes.push(0);
+            basins.labels[start.y * self.width + start.x] = Some(label);
+            to_visit.push_back(start);
+
+            while let Some(current_point) = to_visit.pop_front() {
+                basins.sizes[label] += 1;
+
+                for neighbour in self.get_neighbours_of_point(current_point) {
+                    let index = neighbour.y * self.width + neighbour.x;
 
-            if is_lowpoint {
-                low_points.push(grid_point);
+                    if !self.is_wall(neighbour) && basins.labels[index].is_none() {
+                        basins.labels[index] = Some(label);
+                        to_visit.push_back(neighbour);
+                    }
+                }
             }
         }
-        low_points
+
+        return basins;
     }
 
-    fn calculate_basin_size_for_lowpoint(&self, starting_point: &Point3D) -> usize {
-        let mut to_visit: HashSet<&Point3D> = HashSet::new();
-        let mut basin_points: HashSet<&Point3D> = HashSet::new();
+    /// Basins as the catchments of where water flows: every cell drains to its lowest neighbour,
+    /// flat regions drain together, and regions without a lower neighbour are sinks. Walls are
+    /// ordinary terrain here.
+    fn get_flow_basins(&self) -> FlowBasins<'_> {
+        let (region_of, regions) = self.get_flat_regions();
+
+        // downstream regions at the lowest neighbouring height, empty for sinks
+        let targets: Vec<Vec<usize>> = regions
+            .iter()
+            .map(|region| {
+                let lower = self.get_lower_neighbours(region);
+                let lowest = lower.iter().map(|point| point.z).min();
+
+                let mut lowest_regions: Vec<usize> = lower
+                    .iter()
+                    .filter(|point| Some(point.z) == lowest)
+                    .map(|point| region_of[point.y * self.width + point.x])
+                    .collect();
+                lowest_regions.sort();
+                lowest_regions.dedup();
+
+                return lowest_regions;
+            })
+            .collect();
+
+        let mut sink_of: Vec<Option<usize>> = vec![None; regions.len()];
+        let mut sinks = 0;
+
+        // lower regions get resolved first, so following the first target is always settled
+        let mut by_height: Vec<usize> = (0..regions.len()).collect();
+        by_height.sort_by_key(|region| regions[*region][0].z);
+
+        for region in by_height.iter() {
+            sink_of[*region] = match targets[*region].first() {
+                Some(target) => sink_of[*target],
+                None => {
+                    sinks += 1;
+                    Some(sinks - 1)
+                },
+            };
+        }
 
-        to_visit.insert(starting_point);
+        let mut flow = FlowBasins {
+            basins: BasinMap {
+                width: self.width,
+                labels: region_of.iter().map(|region| sink_of[*region]).collect(),
+                sizes: vec![0; sinks]
+            },
+            ties: vec![],
+            saddles: vec![]
+        };
 
-        while to_visit.len() > 0 {
-            let current_point = to_visit.iter().next().unwrap().clone();
-            to_visit.remove(current_point);
+        for label in flow.basins.labels.iter().flatten() {
+            flow.basins.sizes[*label] += 1;
+        }
 
-            if current_point.z < 9 {
-                basin_points.insert(current_point);
+        for (region, region_targets) in targets.iter().enumerate() {
+            if region_targets.len() < 2 {
+                continue;
+            }
 
-                let neighbours: HashSet<&Point3D> = HashSet::from_iter(self.get_neighbours_of_point(current_point).into_iter());
-                let unvisited: Vec<&Point3D> = neighbours.difference(&basin_points).map(|x| *x).collect();
+            flow.ties.extend(regions[region].iter());
 
-                to_visit.extend(unvisited.into_iter());
+            if region_targets.iter().any(|target| sink_of[*target] != sink_of[region_targets[0]]) {
+                flow.saddles.extend(regions[region].iter());
             }
         }
-        
-        return basin_points.len();
+
+        return flow;
     }
 }
 
-impl TryFrom<&str> for Heightmap {
+impl Heightmap {
+    /// Colour of a cell: its basin's colour lightened with height, grey for walls and white for
+    /// low points.
+    fn get_cell_colour(&self, point: &Point3D, basins: &BasinMap, low_points: &[&Point3D]) -> (u8, u8, u8) {
+        if low_points.contains(&point) {
+            return (255, 255, 255);
+        }
+
+        let (r, g, b) = match basins.get_label(point.x, point.y) {
+            Some(label) => get_basin_colour(label),
+            None => return (64, 64, 64),
+        };
+
+        let wall_height = self.rules.wall_height.max(1);
+        let lighten = |c: u8| (c as u32 + (255 - c as u32) * point.z.min(wall_height) / (2 * wall_height)) as u8;
+
+        return (lighten(r), lighten(g), lighten(b));
+    }
+
+    /// Heights on basin-coloured backgrounds using 24-bit ANSI colours, low points in bold red.
+    fn render_ansi(&self, basins: &BasinMap, low_points: &[&Point3D]) -> String {
+        let mut rendered = String::new();
+
+        for row in self.data.iter() {
+            for point in row.iter() {
+                let (r, g, b) = self.get_cell_colour(point, basins, low_points);
+                let height = std::char::from_digit(point.z, 36).unwrap_or('#');
+
+                if low_points.contains(&point) {
+                    rendered += &format!("\x1b[1;31;48;2;{};{};{}m{}", r, g, b, height);
+                } else {
+                    rendered += &format!("\x1b[0;30;48;2;{};{};{}m{}", r, g, b, height);
+                }
+            }
+            rendered += "\x1b[0m\n";
+        }
+
+        return rendered;
+    }
+
+    /// Binary PPM image with `scale` pixels per cell.
+    fn to_ppm(&self, basins: &BasinMap, low_points: &[&Point3D], scale: usize) -> Vec<u8> {
+        let mut image = format!("P6\n{} {}\n255\n", self.width * scale, self.height * scale).into_bytes();
+
+        for row in self.data.iter() {
+            let colours: Vec<(u8, u8, u8)> = row.iter().map(|point| self.get_cell_colour(point, basins, low_points)).collect();
+
+            for _ in 0..scale {
+                for (r, g, b) in colours.iter() {
+                    for _ in 0..scale {
+                        image.extend([*r, *g, *b]);
+                    }
+                }
+            }
+        }
+
+        return image;
+    }
+}
+
+/// Distinct colours for neighbouring labels by stepping around the hue circle by the golden angle.
+fn get_basin_colour(label: usize) -> (u8, u8, u8) {
+    let hue = (label as f64 * 137.507_764) % 360.0;
+    let sector = hue / 60.0;
+    let falling = 1.0 - (sector % 2.0 - 1.0).abs();
+
+    let (r, g, b) = match sector as u32 {
+        0 => (1.0, falling, 0.0),
+        1 => (falling, 1.0, 0.0),
+        2 => (0.0, 1.0, falling),
+        3 => (0.0, falling, 1.0),
+        4 => (falling, 0.0, 1.0),
+        _ => (1.0, 0.0, falling),
+    };
+
+    // keep the colours dark enough for the height lightening to show
+    let channel = |c: f64| (40.0 + c * 160.0) as u8;
+    return (channel(r), channel(g), channel(b));
+}
+
+/// Catchments of the sinks of a heightmap.
+struct FlowBasins<'a> {
+    basins: BasinMap,
+    /// Cells with more than one steepest way down.
+    ties: Vec<&'a Point3D>,
+    /// Tied cells whose ways down end up in different basins.
+    saddles: Vec<&'a Point3D>
+}
+
+/// Basin labels of every cell of a heightmap.
+#[derive(Clone)]
+struct BasinMap {
+    width: usize,
+    /// Basin of each cell in row-major order, `None` for walls.
+    labels: Vec<Option<usize>>,
+    /// Number of cells in each basin.
+    sizes: Vec<usize>
+}
+
+impl BasinMap {
+    fn get_label(&self, x: usize, y: usize) -> Option<usize> {
+        return self.labels[y * self.width + x];
+    }
+}
+
+/// How the heights of a heightmap are written down.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum HeightFormat {
+    /// One decimal digit per cell.
+    Digits,
+    /// Integers separated by whitespace or commas.
+    Integers,
+    /// One letter per cell, `a` being the lowest and `z` the highest.
+    Letters,
+}
+
+impl HeightFormat {
+    /// Separators mean integers, otherwise letters if there are no digits, otherwise digits.
+    fn detect(value: &str) -> Self {
+        if value.lines().any(|line| line.trim().contains(|c: char| c == ',' || c.is_whitespace())) {
+            return HeightFormat::Integers;
+        }
+
+        if !value.chars().any(|c| c.is_ascii_digit()) {
+            return HeightFormat::Letters;
+        }
+
+        return HeightFormat::Digits;
+    }
+
+    /// Heights of a line along with the column they start at.
+    fn parse_line(&self, line: &str) -> Vec<(usize, Option<u32>, String)> {
+        return match self {
+            HeightFormat::Digits => line
+                .chars()
+                .enumerate()
+                .map(|(column, c)| (column, c.to_digit(10), c.to_string()))
+                .collect(),
+            HeightFormat::Letters => line
+                .chars()
+                .enumerate()
+                .map(|(column, c)| (column, c.is_ascii_lowercase().then(|| c as u32 - 'a' as u32), c.to_string()))
+                .collect(),
+            HeightFormat::Integers => {
+                let mut tokens = vec![];
+                let mut start = None;
+
+                // columns count characters, slicing the line needs their byte offsets
+                let characters = line.char_indices().chain(std::iter::once((line.len(), ',')));
+
+                for (column, (offset, c)) in characters.enumerate() {
+                    match (start, c == ',' || c.is_whitespace()) {
+                        (None, false) => start = Some((column, offset)),
+                        (Some((from_column, from)), true) => {
+                            tokens.push((from_column, line[from..offset].parse().ok(), line[from..offset].to_string()));
+                            start = None;
+                        },
+                        _ => (),
+                    }
+                }
+
+                tokens
+            },
+        };
+    }
+}
+
+impl TryFrom<&str> for HeightFormat {
     type Error = Box<dyn Error>;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let mut data = vec![];
+        match value {
+            "digits" => Ok(HeightFormat::Digits),
+            "integers" => Ok(HeightFormat::Integers),
+            "letters" => Ok(HeightFormat::Letters),
+            _ => Err(format!("Unknown height format \"{}\"", value).into()),
+        }
+    }
+}
 
-        let discovered_width: usize = match value.lines().next() {
-            Some(first_line) => first_line.len(),
-            None => return Err("Can't work on an empty string!".into()),
-        };
+impl Heightmap {
+    /// Rows and columns in error messages count from 1.
+    fn parse(value: &str, format: HeightFormat) -> Result<Self, Box<dyn Error>> {
+        let mut data: Vec<Vec<Point3D>> = vec![];
+
+        if value.trim().is_empty() {
+            return Err("Can't work on an empty string!".into());
+        }
 
         for (y, line) in value.lines().enumerate() {
             let mut line_heights = vec![];
 
-            for (x, height_value) in line.chars().enumerate() {
-                 if height_value.is_numeric() {
-                    line_heights.push(
-                        Point3D::new(
-                            x, 
-                            y, 
-                            height_value.to_digit(10).unwrap() as u8
-                    ));
-                 }
+            for (column, height, token) in format.parse_line(line) {
+                match height {
+                    Some(z) => line_heights.push(Point3D::new(line_heights.len(), y, z)),
+                    None => return Err(format!("Unknown height \"{}\" at row {}, column {}", token, y + 1, column + 1).into()),
+                }
             }
 
-            if line_heights.len() != discovered_width {
-                return Err(
-                    format!("Length of line {} doesn't match length {} of the first line!", line_heights.len(), discovered_width).into()
-                );
+            if let Some(first_line) = data.first() {
+                if line_heights.len() != first_line.len() {
+                    return Err(
+                        format!("Row {} has {} heights, but the first row has {}!", y + 1, line_heights.len(), first_line.len()).into()
+                    );
+                }
             }
 
             data.push(line_heights);
@@ -123,13 +478,22 @@ impl TryFrom<&str> for Heightmap {
         return Ok(
             Heightmap {
                 height: data.len(),
-                width: discovered_width,
+                width: data[0].len(),
                 data,
+                rules: TerrainRules::standard(),
             }
         );
     }
 }
 
+impl TryFrom<&str> for Heightmap {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        return Heightmap::parse(value, HeightFormat::detect(value));
+    }
+}
+
 
 fn main() {
     let matches = App::new("Advent of Code Day 9")
@@ -137,29 +501,116 @@ fn main() {
                         .help("Input file to parse.")
                         .required(true)
                         .index(1))
+                    .arg(Arg::with_name("WALL_HEIGHT")
+                        .help("Height from which on cells separate basins.")
+                        .long("wall-height")
+                        .takes_value(true)
+                        .default_value("9"))
+                    .arg(Arg::with_name("CONNECTIVITY")
+                        .help("Whether cells touch their 4 orthogonal or all 8 surrounding neighbours.")
+                        .long("connectivity")
+                        .takes_value(true)
+                        .possible_values(&["4", "8"])
+                        .default_value("4"))
+                    .arg(Arg::with_name("PLATEAUS")
+                        .help("Count flat regions lower than their surroundings as low points.")
+                        .long("plateaus"))
+                    .arg(Arg::with_name("FLOW")
+                        .help("Define basins by where water flows (steepest descent) instead of by walls.")
+                        .long("flow"))
+                    .arg(Arg::with_name("RENDER")
+                        .help("Print the heightmap with one colour per basin and low points highlighted.")
+                        .long("render"))
+                    .arg(Arg::with_name("IMAGE")
+                        .help("Write the basins as a PPM image.")
+                        .long("image")
+                        .takes_value(true)
+                        .value_name("FILE"))
+                    .arg(Arg::with_name("IMAGE_SCALE")
+                        .help("Width and height of a cell in the image in pixels.")
+                        .long("image-scale")
+                        .takes_value(true)
+                        .default_value("8"))
+                    .arg(Arg::with_name("FORMAT")
+                        .help("How heights are written: one digit or letter per cell, or separated integers. Detected from the input by default.")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["digits", "integers", "letters"]))
                     .get_matches();
 
-    let input = std::fs::read_to_string(&matches.value_of("INPUT").unwrap())
+    let input = std::fs::read_to_string(matches.value_of("INPUT").unwrap())
         .expect("Failed to open the input file");
 
-    let map: Heightmap = input
-        .trim_end()
-        .try_into()
-        .unwrap();
+    let rules = TerrainRules {
+        wall_height: matches.value_of("WALL_HEIGHT").unwrap().parse().expect("Failed to parse the wall height"),
+        connectivity: matches.value_of("CONNECTIVITY").unwrap().try_into().unwrap(),
+        plateau_low_points: matches.is_present("PLATEAUS"),
+    };
+
+    let format = match matches.value_of("FORMAT") {
+        Some(format) => format.try_into().unwrap(),
+        None => HeightFormat::detect(input.trim_end()),
+    };
+
+    let map: Heightmap = Heightmap::parse(input.trim_end(), format)
+        .unwrap()
+        .with_rules(rules);
 
     let low_points = map.get_low_points();
 
     let low_point_risk_sum: usize = low_points.iter().fold(0, |acc, p| acc + p.z as usize + 1);
 
-    let mut basin_sizes: Vec<usize> = low_points.iter().map(|lp| map.calculate_basin_size_for_lowpoint(*lp)).collect();
+    let flow = match matches.is_present("FLOW") {
+        true => Some(map.get_flow_basins()),
+        false => None,
+    };
+
+    let basins = match &flow {
+        Some(flow) => flow.basins.clone(),
+        None => map.get_basin_map(),
+    };
+
+    let mut basin_sizes = basins.sizes.clone();
     basin_sizes.sort();
     basin_sizes.reverse();
 
-    let product_of_three_largest_basins = basin_sizes.iter().take(3).fold(1, |acc, size| acc * size);
+    let product_of_three_largest_basins: usize = basin_sizes.iter().take(3).product();
+
+    let mut drained_basins: Vec<bool> = vec![false; basins.sizes.len()];
+    for low_point in low_points.iter() {
+        if let Some(label) = basins.get_label(low_point.x, low_point.y) {
+            drained_basins[label] = true;
+        }
+    }
+    let basins_without_low_point = drained_basins.iter().filter(|drained| !**drained).count();
 
     println!("Sum of risk levels of all lowpoints: {}", low_point_risk_sum);
     println!("Product of sizes of three largest basins: {}", product_of_three_largest_basins);
 
+    if basins_without_low_point > 0 {
+        println!("Basins without a low point: {}", basins_without_low_point);
+    }
+
+    if matches.is_present("RENDER") {
+        print!("{}", map.render_ansi(&basins, &low_points));
+    }
+
+    if let Some(image_path) = matches.value_of("IMAGE") {
+        let scale: usize = matches.value_of("IMAGE_SCALE").unwrap().parse().expect("Failed to parse the image scale");
+
+        std::fs::write(image_path, map.to_ppm(&basins, &low_points, scale))
+            .expect("Failed to write the image");
+    }
+
+    if let Some(flow) = flow {
+        println!("Cells with tied steepest descents: {}", flow.ties.len());
+        println!("Saddle points: {}", flow.saddles.len());
+
+        for saddle in flow.saddles.iter() {
+            println!("  ({}, {}) at height {}", saddle.x, saddle.y, saddle.z);
+        }
+    }
+
 }
 
 #[cfg(test)]
@@ -193,9 +644,136 @@ fn test_example_lowpoint_risk() {
 #[test]
 fn test_example_basin_size() {
     let map: Heightmap = EXAMPLE_STRING.try_into().unwrap();
+    let basins = map.get_basin_map();
+
+    assert_eq!(basins.sizes[basins.get_label(1, 0).unwrap()], 3);
+    assert_eq!(basins.sizes[basins.get_label(9, 0).unwrap()], 9);
+    assert_eq!(basins.sizes[basins.get_label(2, 2).unwrap()], 14);
+    assert_eq!(basins.sizes[basins.get_label(6, 4).unwrap()], 9);
+}
+
+#[test]
+fn test_basin_map() {
+    let map: Heightmap = EXAMPLE_STRING.try_into().unwrap();
+    let basins = map.get_basin_map();
+
+    let mut sizes = basins.sizes.clone();
+    sizes.sort();
+    assert_eq!(sizes, vec![3, 9, 9, 14]);
+    assert_eq!(basins.labels.iter().filter(|x| x.is_none()).count(), 50 - 35);
+    assert_eq!(basins.get_label(0, 0), basins.get_label(1, 0));
+    assert_ne!(basins.get_label(1, 0), basins.get_label(9, 0));
+
+    // a flat basin has no strict low point but still gets labelled
+    let map: Heightmap = "92291\n99999".try_into().unwrap();
+    assert_eq!(map.get_low_points().len(), 1);
+    assert_eq!(map.get_basin_map().sizes, vec![2, 1]);
+}
+
+#[test]
+fn test_terrain_rules() {
+    let map: Heightmap = "92291\n99999\n11911".try_into().unwrap();
+    assert_eq!(map.get_basin_map().sizes, vec![2, 1, 2, 2]);
+    assert_eq!(map.get_low_points().len(), 1);
+
+    let rules = TerrainRules {
+        wall_height: 9,
+        connectivity: Connectivity::Four,
+        plateau_low_points: true
+    };
+    let map = map.with_rules(rules);
+
+    let regions = map.get_low_regions();
+    assert_eq!(regions.len(), 4);
+    assert_eq!(regions[0], vec![&Point3D::new(1, 0, 2), &Point3D::new(2, 0, 2)]);
+    assert_eq!(map.get_low_points().len(), 7);
+
+    // diagonal neighbours only join basins with 8-connectivity
+    let map: Heightmap = "19\n91".try_into().unwrap();
+    assert_eq!(map.get_basin_map().sizes, vec![1, 1]);
+    let map = map.with_rules(TerrainRules { connectivity: Connectivity::Eight, ..rules });
+    assert_eq!(map.get_basin_map().sizes, vec![2]);
+    assert_eq!(map.get_low_points().len(), 2);
+
+    // lowering the walls splits basins
+    let map: Heightmap = "123\n456".try_into().unwrap();
+    assert_eq!(map.get_basin_map().sizes, vec![6]);
+    let map = map.with_rules(TerrainRules { wall_height: 3, ..rules });
+    assert_eq!(map.get_basin_map().sizes, vec![2]);
+}
+
+#[test]
+fn test_flow_basins() {
+    let map: Heightmap = EXAMPLE_STRING.try_into().unwrap();
+    let flow = map.get_flow_basins();
+
+    // every cell drains somewhere, so the sizes cover the whole map
+    assert_eq!(flow.basins.sizes.iter().sum::<usize>(), 50);
+    assert_eq!(flow.basins.sizes.len(), 4);
+    assert_eq!(flow.basins.get_label(2, 0), flow.basins.get_label(1, 0));
 
-    assert_eq!(map.calculate_basin_size_for_lowpoint(&Point3D::new(1, 0, 1)), 3);
-    assert_eq!(map.calculate_basin_size_for_lowpoint(&Point3D::new(9, 0, 0)), 9);
-    assert_eq!(map.calculate_basin_size_for_lowpoint(&Point3D::new(2, 2, 5)), 14);
-    assert_eq!(map.calculate_basin_size_for_lowpoint(&Point3D::new(6, 4, 5)), 9);
-}
\ No newline at end of file
+    // the 5 between the two low points of a valley drains both ways
+    let map: Heightmap = "13531".try_into().unwrap();
+    let flow = map.get_flow_basins();
+
+    assert_eq!(flow.basins.sizes, vec![3, 2]);
+    assert_eq!(flow.basins.get_label(2, 0), Some(0));
+    assert_eq!(flow.ties, vec![&Point3D::new(2, 0, 5)]);
+    assert_eq!(flow.saddles, vec![&Point3D::new(2, 0, 5)]);
+
+    // a terrace drains as one towards its lowest neighbour
+    let map: Heightmap = "0333\n9992".try_into().unwrap();
+    let flow = map.get_flow_basins();
+
+    assert!(flow.ties.is_empty());
+    assert_eq!(flow.basins.get_label(1, 0), flow.basins.get_label(0, 0));
+    assert_eq!(flow.basins.get_label(3, 0), flow.basins.get_label(0, 0));
+}
+
+#[test]
+fn test_rendering() {
+    let map: Heightmap = "195\n999".try_into().unwrap();
+    let basins = map.get_basin_map();
+    let low_points = map.get_low_points();
+
+    let rendered = map.render_ansi(&basins, &low_points);
+    assert_eq!(rendered.lines().count(), 2);
+    assert!(rendered.starts_with("\x1b[1;31;48;2;255;255;255m1"));
+    assert!(rendered.contains("\x1b[0;30;48;2;64;64;64m9"));
+
+    let image = map.to_ppm(&basins, &low_points, 2);
+    let header = b"P6\n6 4\n255\n";
+    assert_eq!(&image[..header.len()], header);
+    assert_eq!(image.len(), header.len() + 6 * 4 * 3);
+    assert_eq!(&image[header.len()..header.len() + 6], &[255; 6]);
+
+    assert_ne!(get_basin_colour(0), get_basin_colour(1));
+}
+
+#[test]
+fn test_height_formats() {
+    let map: Heightmap = "10, 200, 3\n4 5 60".try_into().unwrap();
+    assert_eq!(map.width, 3);
+    assert_eq!(map.data[0][1], Point3D::new(1, 0, 200));
+    assert_eq!(map.data[1][2], Point3D::new(2, 1, 60));
+
+    let map: Heightmap = "abz\nqrs".try_into().unwrap();
+    assert_eq!(map.data[0][2], Point3D::new(2, 0, 25));
+    assert_eq!(map.data[1][0], Point3D::new(0, 1, 16));
+
+    let error = Heightmap::try_from("219\n3x8").err().unwrap();
+    assert_eq!(error.to_string(), "Unknown height \"x\" at row 2, column 2");
+
+    let error = Heightmap::parse("1 2\n3 -4", HeightFormat::Integers).err().unwrap();
+    assert_eq!(error.to_string(), "Unknown height \"-4\" at row 2, column 3");
+
+    let error = Heightmap::parse("1 2 3\n4 é 6", HeightFormat::Integers).err().unwrap();
+    assert_eq!(error.to_string(), "Unknown height \"é\" at row 2, column 3");
+
+    // the ideographic space separating 4 and 5 takes three bytes
+    let error = Heightmap::parse("1 2 3\n4\u{3000}5 x", HeightFormat::Integers).err().unwrap();
+    assert_eq!(error.to_string(), "Unknown height \"x\" at row 2, column 5");
+
+    let error = Heightmap::try_from("219\n38").err().unwrap();
+    assert_eq!(error.to_string(), "Row 2 has 2 heights, but the first row has 3!");
+}
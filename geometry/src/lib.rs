@@ -0,0 +1,519 @@
+//! Integer 2D geometry shared between the puzzle days.
+
+#![allow(clippy::needless_return)]
+
+use std::{error::Error, cmp::{min, max}, ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign}};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Vector2D {
+    pub x: i32,
+    pub y: i32
+}
+
+impl Vector2D {
+    pub fn new(x: i32, y: i32) -> Self {
+        return Vector2D {
+            x,y
+        };
+    }
+
+    pub fn zero() -> Self {
+        return Vector2D::new(0, 0);
+    }
+
+    pub fn cross(&self, other: &Vector2D) -> i64 {
+        return self.x as i64 * other.y as i64 - self.y as i64 * other.x as i64;
+    }
+
+    pub fn dot(&self, other: &Vector2D) -> i64 {
+        return self.x as i64 * other.x as i64 + self.y as i64 * other.y as i64;
+    }
+
+    pub fn manhattan_distance(&self, other: &Vector2D) -> i64 {
+        return (self.x as i64 - other.x as i64).abs() + (self.y as i64 - other.y as i64).abs();
+    }
+
+    pub fn chebyshev_distance(&self, other: &Vector2D) -> i64 {
+        return max((self.x as i64 - other.x as i64).abs(), (self.y as i64 - other.y as i64).abs());
+    }
+
+    /// Rotates by 90°, counter-clockwise with the y axis pointing up.
+    pub fn rotate_left(&self) -> Self {
+        return Vector2D::new(-self.y, self.x);
+    }
+
+    /// Rotates by 90°, clockwise with the y axis pointing up.
+    pub fn rotate_right(&self) -> Self {
+        return Vector2D::new(self.y, -self.x);
+    }
+}
+
+impl Add<Vector2D> for Vector2D {
+    type Output = Vector2D;
+
+    fn add(self, rhs: Vector2D) -> Self::Output {
+        return Vector2D {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y
+        };
+    }
+}
+
+impl AddAssign<Vector2D> for Vector2D {
+    fn add_assign(&mut self, rhs: Vector2D) {
+        self.x += rhs.x;
+        self.y += rhs.y;
+    }
+}
+
+impl Sub<Vector2D> for Vector2D {
+    type Output = Vector2D;
+
+    fn sub(self, rhs: Vector2D) -> Self::Output {
+        return Vector2D {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y
+        };
+    }
+}
+
+impl SubAssign<Vector2D> for Vector2D {
+    fn sub_assign(&mut self, rhs: Vector2D) {
+        self.x -= rhs.x;
+        self.y -= rhs.y;
+    }
+}
+
+impl Mul<i32> for Vector2D {
+    type Output = Vector2D;
+
+    fn mul(self, rhs: i32) -> Self::Output {
+        return Vector2D {
+            x: self.x * rhs,
+            y: self.y * rhs
+        };
+    }
+}
+
+impl MulAssign<i32> for Vector2D {
+    fn mul_assign(&mut self, rhs: i32) {
+        self.x *= rhs;
+        self.y *= rhs;
+    }
+}
+
+impl Neg for Vector2D {
+    type Output = Vector2D;
+
+    fn neg(self) -> Self::Output {
+        return Vector2D::new(-self.x, -self.y);
+    }
+}
+
+impl TryFrom<&str> for Vector2D {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.split_once(',') {
+            Some((x, y)) => Ok(Vector2D::new(x.parse()?, y.parse()?)),
+            None => Err(format!("Failed to parse point: \"{}\"", value).into()),
+        }
+    }
+}
+
+/// Axis aligned box, inclusive on both ends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoundingBox {
+    pub min: Vector2D,
+    pub max: Vector2D
+}
+
+impl BoundingBox {
+    pub fn new(min: Vector2D, max: Vector2D) -> Self {
+        return BoundingBox {
+            min,
+            max
+        };
+    }
+
+    /// `None` if there are no points.
+    pub fn from_points(points: impl IntoIterator<Item = Vector2D>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+
+        return Some(points.fold(BoundingBox::new(first, first), |acc, point| acc.include(point)));
+    }
+
+    pub fn include(&self, point: Vector2D) -> Self {
+        return BoundingBox {
+            min: Vector2D::new(min(self.min.x, point.x), min(self.min.y, point.y)),
+            max: Vector2D::new(max(self.max.x, point.x), max(self.max.y, point.y))
+        };
+    }
+
+    pub fn union(&self, other: &BoundingBox) -> Self {
+        return self.include(other.min).include(other.max);
+    }
+
+    pub fn contains(&self, point: &Vector2D) -> bool {
+        return self.min.x <= point.x && point.x <= self.max.x && self.min.y <= point.y && point.y <= self.max.y;
+    }
+
+    pub fn get_width(&self) -> i64 {
+        return self.max.x as i64 - self.min.x as i64 + 1;
+    }
+
+    pub fn get_height(&self) -> i64 {
+        return self.max.y as i64 - self.min.y as i64 + 1;
+    }
+
+    /// A box spanning the whole i32 range on both axes has an area beyond i64.
+    pub fn get_area(&self) -> i128 {
+        return self.get_width() as i128 * self.get_height() as i128;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineClass {
+    Horizontal,
+    Vertical,
+    Diagonal,
+    Arbitrary
+}
+
+impl TryFrom<&str> for LineClass {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "horizontal" => Ok(LineClass::Horizontal),
+            "vertical" => Ok(LineClass::Vertical),
+            "diagonal" => Ok(LineClass::Diagonal),
+            "arbitrary" => Ok(LineClass::Arbitrary),
+            _ => Err(format!("Unknown line class \"{}\"", value).into()),
+        }
+    }
+}
+
+/// Line segment, including both end points.
+///
+/// The end points may be at most `i32::MAX` apart along each axis, which parsing checks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line2D {
+    pub from: Vector2D,
+    pub to: Vector2D
+}
+
+impl Line2D {
+    pub fn new(from: Vector2D, to: Vector2D) -> Self {
+        return Line2D {
+            from,
+            to
+        };
+    }
+
+    /// Smallest integer step from one lattice point of the line to the next.
+    pub fn get_integer_step_vector(&self) -> Vector2D {
+        let dir_vector = self.to - self.from;
+        let step_count = self.get_step_count();
+
+        if step_count == 0 {
+            return Vector2D::zero();
+        }
+
+        return Vector2D {
+            x: dir_vector.x / step_count,
+            y: dir_vector.y / step_count
+        };
+    }
+
+    /// Number of steps of `get_integer_step_vector` needed to get from `from` to `to`.
+    pub fn get_step_count(&self) -> i32 {
+        let dir_vector = self.to - self.from;
+        return gcd(dir_vector.x.abs(), dir_vector.y.abs());
+    }
+
+    /// All lattice points of the line, starting at `from`.
+    pub fn get_points(&self) -> impl Iterator<Item = Vector2D> {
+        let from = self.from;
+        let step = self.get_integer_step_vector();
+
+        return (0..=self.get_step_count()).map(move |k| from + step * k);
+    }
+
+    pub fn length_squared(&self) -> i64 {
+        let dir_vector = self.to - self.from;
+        return dir_vector.dot(&dir_vector);
+    }
+
+    pub fn get_class(&self) -> LineClass {
+        let dir_vector = self.to - self.from;
+
+        if dir_vector.y == 0 {
+            return LineClass::Horizontal;
+        } else if dir_vector.x == 0 {
+            return LineClass::Vertical;
+        } else if dir_vector.x.abs() == dir_vector.y.abs() {
+            return LineClass::Diagonal;
+        } else {
+            return LineClass::Arbitrary;
+        }
+    }
+
+    pub fn get_bounding_box(&self) -> BoundingBox {
+        return BoundingBox::new(self.from, self.from).include(self.to);
+    }
+
+    pub fn contains_point(&self, point: &Vector2D) -> bool {
+        let dir_vector = get_wide_offset(&self.from, &self.to);
+        let relative = get_wide_offset(&self.from, point);
+
+        if dir_vector == (0, 0) {
+            return relative == dir_vector;
+        }
+
+        let projection = wide_dot(relative, dir_vector);
+
+        return wide_cross(relative, dir_vector) == 0 && projection >= 0 && projection <= wide_dot(dir_vector, dir_vector);
+    }
+
+    /// Whether the segments share any point, not necessarily an integer one.
+    pub fn intersects(&self, other: &Line2D) -> bool {
+        let orientation = |a: Vector2D, b: Vector2D, c: Vector2D| wide_cross(get_wide_offset(&a, &b), get_wide_offset(&a, &c)).signum();
+
+        let o1 = orientation(self.from, self.to, other.from);
+        let o2 = orientation(self.from, self.to, other.to);
+        let o3 = orientation(other.from, other.to, self.from);
+        let o4 = orientation(other.from, other.to, self.to);
+
+        if o1 != o2 && o3 != o4 {
+            return true;
+        }
+
+        // collinear or touching cases
+        return self.contains_point(&other.from)
+            || self.contains_point(&other.to)
+            || other.contains_point(&self.from)
+            || other.contains_point(&self.to);
+    }
+
+    /// All integer points covered by both this line and `other`.
+    pub fn get_lattice_intersections(&self, other: &Line2D) -> Vec<Vector2D> {
+        let own_dir = get_wide_offset(&self.from, &self.to);
+        let other_dir = get_wide_offset(&other.from, &other.to);
+
+        if own_dir == (0, 0) {
+            return if other.contains_point(&self.from) { vec![self.from] } else { vec![] };
+        }
+
+        if other_dir == (0, 0) {
+            return if self.contains_point(&other.from) { vec![other.from] } else { vec![] };
+        }
+
+        let offset = get_wide_offset(&self.from, &other.from);
+        let mut denominator = wide_cross(own_dir, other_dir);
+
+        if denominator == 0 {
+            if wide_cross(offset, own_dir) != 0 {
+                // parallel, but not on the same infinite line
+                return vec![];
+            }
+
+            // collinear: express the other line's endpoints as step counts along this line
+            let step = self.get_integer_step_vector();
+            let wide_step = (step.x as i128, step.y as i128);
+            let step_length = wide_dot(wide_step, wide_step);
+            let other_from = wide_dot(offset, wide_step) / step_length;
+            let other_to = wide_dot(get_wide_offset(&self.from, &other.to), wide_step) / step_length;
+
+            let first = max(0, min(other_from, other_to));
+            let last = min(self.get_step_count() as i128, max(other_from, other_to));
+
+            return (first..=last)
+                .map(|k| self.from + step * k as i32)
+                .collect();
+        }
+
+        // solve self.from + t * own_dir == other.from + u * other_dir with t, u in [0, 1]
+        let mut t_numerator = wide_cross(offset, other_dir);
+        let mut u_numerator = wide_cross(offset, own_dir);
+
+        if denominator < 0 {
+            denominator = -denominator;
+            t_numerator = -t_numerator;
+            u_numerator = -u_numerator;
+        }
+
+        if t_numerator < 0 || t_numerator > denominator || u_numerator < 0 || u_numerator > denominator {
+            return vec![];
+        }
+
+        let x_offset = own_dir.0 * t_numerator;
+        let y_offset = own_dir.1 * t_numerator;
+
+        if x_offset % denominator != 0 || y_offset % denominator != 0 {
+            // the lines cross between two integer points
+            return vec![];
+        }
+
+        // the intersection lies on the segment, so it's back in i32 range
+        return vec![Vector2D::new(
+            (self.from.x as i128 + x_offset / denominator) as i32,
+            (self.from.y as i128 + y_offset / denominator) as i32
+        )];
+    }
+}
+
+/// Parses `x1,y1 -> x2,y2`.
+impl TryFrom<&str> for Line2D {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.split_once(" -> ") {
+            Some((from, to)) => {
+                let line = Line2D::new(from.try_into()?, to.try_into()?);
+                let (dx, dy) = get_wide_offset(&line.from, &line.to);
+
+                if dx.abs() > i32::MAX as i128 || dy.abs() > i32::MAX as i128 {
+                    return Err(format!("Line spans more than {} along an axis: \"{}\"", i32::MAX, value).into());
+                }
+
+                Ok(line)
+            },
+            None => Err(format!("Failed to parse line: \"{}\"", value).into()),
+        }
+    }
+}
+
+/// `to - from`, wide enough for points at opposite ends of the i32 range and products of such offsets.
+fn get_wide_offset(from: &Vector2D, to: &Vector2D) -> (i128, i128) {
+    return (to.x as i128 - from.x as i128, to.y as i128 - from.y as i128);
+}
+
+fn wide_cross(a: (i128, i128), b: (i128, i128)) -> i128 {
+    return a.0 * b.1 - a.1 * b.0;
+}
+
+fn wide_dot(a: (i128, i128), b: (i128, i128)) -> i128 {
+    return a.0 * b.0 + a.1 * b.1;
+}
+
+pub fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        return a;
+    }
+
+    return gcd(b, a % b);
+}
+
+#[test]
+fn test_vector_operators() {
+    let a = Vector2D::new(3, -2);
+    let b = Vector2D::new(-1, 5);
+
+    assert_eq!(a + b, Vector2D::new(2, 3));
+    assert_eq!(a - b, Vector2D::new(4, -7));
+    assert_eq!(a * 3, Vector2D::new(9, -6));
+    assert_eq!(-a, Vector2D::new(-3, 2));
+
+    let mut c = a;
+    c += b;
+    c -= a;
+    c *= 2;
+    assert_eq!(c, Vector2D::new(-2, 10));
+}
+
+#[test]
+fn test_distances() {
+    let a = Vector2D::new(1, 1);
+    let b = Vector2D::new(-2, 5);
+
+    assert_eq!(a.manhattan_distance(&b), 7);
+    assert_eq!(a.chebyshev_distance(&b), 4);
+}
+
+#[test]
+fn test_rotation() {
+    let v = Vector2D::new(2, 1);
+
+    assert_eq!(v.rotate_left(), Vector2D::new(-1, 2));
+    assert_eq!(v.rotate_right(), Vector2D::new(1, -2));
+    assert_eq!(v.rotate_left().rotate_left().rotate_left().rotate_left(), v);
+    assert_eq!(v.rotate_left().rotate_right(), v);
+}
+
+#[test]
+fn test_bounding_box() {
+    let bounds = BoundingBox::from_points(vec![Vector2D::new(3, -1), Vector2D::new(-2, 4), Vector2D::new(0, 0)]).unwrap();
+
+    assert_eq!(bounds, BoundingBox::new(Vector2D::new(-2, -1), Vector2D::new(3, 4)));
+    assert_eq!(bounds.get_width(), 6);
+    assert_eq!(bounds.get_height(), 6);
+    assert!(bounds.contains(&Vector2D::new(3, 4)));
+    assert!(!bounds.contains(&Vector2D::new(4, 4)));
+    assert_eq!(BoundingBox::from_points(vec![]), None);
+}
+
+#[test]
+fn test_line_points() {
+    let line: Line2D = "0,0 -> 4,2".try_into().unwrap();
+
+    assert_eq!(line.get_points().collect::<Vec<Vector2D>>(), vec![Vector2D::new(0, 0), Vector2D::new(2, 1), Vector2D::new(4, 2)]);
+    assert_eq!(line.length_squared(), 20);
+    assert!(line.contains_point(&Vector2D::new(2, 1)));
+    assert!(!line.contains_point(&Vector2D::new(6, 3)));
+}
+
+#[test]
+fn test_segment_intersection_predicate() {
+    let line: Line2D = "0,0 -> 4,4".try_into().unwrap();
+
+    let crossing: Line2D = "0,1 -> 1,0".try_into().unwrap();
+    let touching: Line2D = "4,4 -> 9,0".try_into().unwrap();
+    let collinear: Line2D = "3,3 -> 8,8".try_into().unwrap();
+    let parallel: Line2D = "1,0 -> 5,4".try_into().unwrap();
+    let short: Line2D = "3,0 -> 5,-2".try_into().unwrap();
+
+    assert!(line.intersects(&crossing));
+    assert!(line.intersects(&touching));
+    assert!(line.intersects(&collinear));
+    assert!(!line.intersects(&parallel));
+    assert!(!line.intersects(&short));
+
+    // crossing at (0.5, 0.5) isn't a lattice point
+    assert!(line.get_lattice_intersections(&crossing).is_empty());
+}
+
+#[test]
+fn test_parsing() {
+    let res: Line2D = "-1,-2 -> -3,-4".try_into().unwrap();
+
+    assert_eq!(res, Line2D::new(Vector2D::new(-1, -2), Vector2D::new(-3, -4)));
+    assert!(Line2D::try_from("1,2 => 3,4").is_err());
+    assert!(Line2D::try_from("1,2 -> 3").is_err());
+}
+
+#[test]
+fn test_extreme_coordinates() {
+    assert!(Line2D::try_from("-2000000000,0 -> 2000000000,0").is_err());
+    assert!(Line2D::try_from("0,2147483647 -> 0,-1").is_err());
+
+    let full_range = BoundingBox::new(Vector2D::new(i32::MIN, i32::MIN), Vector2D::new(i32::MAX, i32::MAX));
+    assert_eq!(full_range.get_area(), 1i128 << 64);
+
+    let left: Line2D = "-2000000000,0 -> 0,0".try_into().unwrap();
+    let right: Line2D = "0,0 -> 2000000000,0".try_into().unwrap();
+    assert_eq!(left.get_step_count(), 2000000000);
+    assert!(!right.contains_point(&Vector2D::new(-2000000000, 0)));
+    assert!(left.intersects(&right));
+    assert_eq!(left.get_lattice_intersections(&right), vec![Vector2D::zero()]);
+
+    let rising: Line2D = "-2000000000,0 -> 0,2000000000".try_into().unwrap();
+    let falling: Line2D = "0,0 -> -2000000000,2000000000".try_into().unwrap();
+    assert_eq!(rising.get_lattice_intersections(&falling), vec![Vector2D::new(-1000000000, 1000000000)]);
+
+    let far: Line2D = "2000000000,5 -> 2000000010,-5".try_into().unwrap();
+    let near: Line2D = "-2000000000,0 -> -1999999990,0".try_into().unwrap();
+    assert!(!far.intersects(&near));
+    assert!(far.get_lattice_intersections(&near).is_empty());
+}